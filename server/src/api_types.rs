@@ -0,0 +1,290 @@
+// Response shapes of the /api/v1 routes. These are also what the OpenAPI
+// document is generated from, so field changes show up there too.
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::SiteTeamContentUsage;
+use crate::report_format::ReportRows;
+
+#[derive(Default, Serialize, ToSchema)]
+pub struct DriverSessionEntry {
+    pub subsession_id: i64,
+    pub old_irating: i32,
+    pub new_irating: i32,
+    pub old_cpi: f32,
+    pub new_cpi: f32,
+    pub incidents: i32,
+    pub laps_complete: i32,
+    // 1/10000 seconds
+    pub average_lap: i64,
+    // 0 based
+    pub finish_position_in_class: i32,
+    pub car_id: i32,
+    pub track_id: i32,
+    pub package_id: i32,
+    pub license_category: i32,
+    pub start_time: String,
+    pub event_type: i32,
+    pub series_name: String,
+    pub session_name: String,
+    pub simsession_number: i32,
+    pub simsession_type: i32,
+    pub official_session: bool,
+    pub season_year: i32,
+    pub season_quarter: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DriverInfoResponse {
+    pub sessions: Vec<DriverSessionEntry>,
+    // pass as cursor to get the next page, only set if there is one
+    pub next_cursor: Option<String>,
+}
+
+impl ReportRows for DriverInfoResponse {
+    type Row = DriverSessionEntry;
+    fn rows(&self) -> &Vec<DriverSessionEntry> {
+        return &self.sessions;
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TrackEntry {
+    pub package_id: i64,
+    pub track_id: i64,
+    pub track_name: String,
+    pub config_name: String,
+    pub track_config_length: f32,
+    pub corners_per_lap: i32,
+    pub category: i32,
+    pub grid_stalls: i32,
+    pub pit_road_speed_limit: i32,
+    pub number_pitstalls: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CarEntry {
+    pub car_id: i64,
+    pub car_name: String,
+    pub car_name_abbreviated: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TrackDataResponse {
+    pub tracks: Vec<TrackEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TrackCarDataResponse {
+    pub tracks: Vec<TrackEntry>,
+    pub cars: Vec<CarEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CustomerEntry {
+    pub name: String,
+    pub cust_id: i64,
+}
+
+#[derive(Default, Serialize, ToSchema)]
+pub struct TeamResultEntry {
+    pub subsession_id: i64,
+    pub cust_id: i64,
+    pub team_id: i64,
+    pub driver_name: String,
+    pub track_id: i32,
+    pub package_id: i32,
+    pub car_id: i32,
+    pub laps_complete: i32,
+    // 0 based
+    pub finish_position_in_class: i32,
+    pub incidents: i32,
+    pub start_time: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TeamResultsResponse {
+    pub results: Vec<TeamResultEntry>,
+}
+
+impl ReportRows for TeamResultsResponse {
+    type Row = TeamResultEntry;
+    fn rows(&self) -> &Vec<TeamResultEntry> {
+        return &self.results;
+    }
+}
+
+#[derive(Default, Serialize, ToSchema)]
+pub struct SessionResultEntry {
+    // session name, series name if the session has none
+    pub name: String,
+    // YYYY.MM.DD
+    pub date: String,
+    pub track_id: i64,
+    pub car_id: i64,
+    pub cust_id: i64,
+    pub laps_complete: i64,
+    pub incidents: i64,
+    // P1, P2, ... or DNF
+    pub position: String,
+    pub result_url: String,
+}
+
+#[derive(Default, Serialize, ToSchema)]
+pub struct SiteTeamReportEntry {
+    pub display_name: String,
+    pub laps_complete: i64,
+    pub incidents: i64,
+    pub time_on_track: i64,
+    pub distance_driven: f32,
+    pub corners: i64,
+    // road irating
+    pub first_irating: i64,
+    pub last_irating: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SiteTeamReportResponse {
+    pub results: Vec<SiteTeamReportEntry>,
+}
+
+impl ReportRows for SiteTeamReportResponse {
+    type Row = SiteTeamReportEntry;
+    fn rows(&self) -> &Vec<SiteTeamReportEntry> {
+        return &self.results;
+    }
+}
+
+#[derive(Default, Serialize, ToSchema)]
+pub struct DriverPairingEntry {
+    pub driver1: String,
+    pub driver2: String,
+    pub total_time: i64,
+}
+
+#[derive(Default, Serialize, ToSchema)]
+pub struct ContentUsageEntry {
+    pub driver_name: String,
+    // track or car
+    pub content_kind: String,
+    pub content_name: String,
+    // 1/10000 seconds
+    pub time: i64,
+}
+
+// The JSON is the usage maps as they are, the CSV and NDJSON have a row per driver and track or car
+#[derive(Serialize)]
+pub struct ContentUsageResponse {
+    #[serde(flatten)]
+    pub usage: SiteTeamContentUsage,
+    #[serde(skip)]
+    pub entries: Vec<ContentUsageEntry>,
+}
+
+impl ReportRows for ContentUsageResponse {
+    type Row = ContentUsageEntry;
+    fn rows(&self) -> &Vec<ContentUsageEntry> {
+        return &self.entries;
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StandingWeekEntry {
+    // 0 based
+    pub race_week_num: i64,
+    // 0 -> Division 1, etc
+    pub division: i64,
+    // 1 based
+    pub position: i64,
+    pub points: i64,
+    pub starts: i64,
+    pub wins: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DriverStandingsHistory {
+    pub cust_id: i64,
+    pub name: String,
+    pub weeks: Vec<StandingWeekEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StandingsHistoryResponse {
+    pub season_id: i64,
+    pub car_class_id: i64,
+    pub drivers: Vec<DriverStandingsHistory>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CalendarWeek {
+    pub season_id: i64,
+    pub series_id: i64,
+    // 0 based
+    pub race_week_num: i64,
+    pub track_id: i64,
+    pub track_name: String,
+    pub config_name: String,
+    // YYYY-MM-DD
+    pub start_date: String,
+    pub race_lap_limit: Option<i64>,
+    // minutes
+    pub race_time_limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CalendarSeries {
+    pub series_id: i64,
+    pub series_name: String,
+    pub race_count: i64,
+    pub driver_count: i64,
+    pub current_week: Option<CalendarWeek>,
+    pub next_week: Option<CalendarWeek>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CalendarResponse {
+    pub series: Vec<CalendarSeries>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MemberStatsValues {
+    pub starts: i64,
+    pub wins: i64,
+    pub top5: i64,
+    pub poles: i64,
+    pub laps: i64,
+    // only known by iRacing
+    pub laps_led: Option<i64>,
+    // per start
+    pub avg_incidents: f64,
+    // 1 based, in class for the computed stats
+    pub avg_start_position: f64,
+    pub avg_finish_position: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MemberStatsEntry {
+    // null for career totals
+    pub year: Option<i32>,
+    // 1 -> Oval, 2 -> Road, 3 -> Dirt Oval, 4 -> Dirt Road, 5 -> Sports Car, 6 -> Formula Car
+    pub category_id: i32,
+    // iRacing's numbers, null if it has none or they were not synced
+    pub official: Option<MemberStatsValues>,
+    // computed from the official races in the db, null if it has none
+    pub computed: Option<MemberStatsValues>,
+    // official starts missing from the db, negative if the db has more
+    pub missing_starts: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MemberStatsResponse {
+    pub cust_id: i64,
+    // the profile fields are null until the member stats were synced
+    pub display_name: Option<String>,
+    pub member_since: Option<String>,
+    pub club_name: Option<String>,
+    pub synced_at: Option<String>,
+    pub career: Vec<MemberStatsEntry>,
+    pub yearly: Vec<MemberStatsEntry>,
+}
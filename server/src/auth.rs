@@ -0,0 +1,151 @@
+// API tokens and roles for the routes that change data.
+//
+// Clients send `Authorization: Bearer <token>`. Only the SHA-256 of a token is
+// stored, the token itself is printed once when it is created (--create-api-token).
+// Routes require a role by taking one of the guards below as an argument:
+// `ApiUser` (any role), `TeamManagerUser` (team manager or admin) or `AdminUser`.
+// Team managers are scoped to a site team, routes check that with `can_manage_site_team`.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use rocket::http::Status;
+use rocket::outcome::try_outcome;
+use rocket::request::{FromRequest, Outcome, Request};
+use sha2::{Digest, Sha256};
+
+use crate::db::{add_api_token_to_db, query_api_token, ApiToken, DbPool};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Role {
+    Viewer,
+    TeamManager,
+    Admin,
+}
+
+impl Role {
+    pub fn from_str(role: &str) -> Option<Self> {
+        return match role {
+            "viewer" => Some(Role::Viewer),
+            "team-manager" => Some(Role::TeamManager),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        };
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            Role::Viewer => "viewer",
+            Role::TeamManager => "team-manager",
+            Role::Admin => "admin",
+        };
+    }
+}
+
+pub fn hash_token(token: &str) -> String {
+    let hash = Sha256::digest(token.as_bytes());
+    return hash.iter().map(|byte| format!("{byte:02x}")).collect();
+}
+
+// Returns the token, which is not stored anywhere
+pub fn create_api_token(name: &str, role: Role, site_team_name: Option<String>) -> Result<String, String> {
+    if (role == Role::TeamManager) != site_team_name.is_some() {
+        return Err("A site team has to be given for team managers, and only for them".to_owned());
+    }
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+
+    add_api_token_to_db(&ApiToken{
+        token_hash: hash_token(&token),
+        name: name.to_owned(),
+        role: role.as_str().to_owned(),
+        site_team_name,
+        created_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    }).map_err(|error| format!("Could not create token {name}: {error}"))?;
+
+    return Ok(token);
+}
+
+pub struct ApiUser {
+    pub name: String,
+    pub role: Role,
+    // only set for team managers
+    pub site_team_name: Option<String>,
+}
+
+impl ApiUser {
+    pub fn can_manage_site_team(&self, site_team_name: &str) -> bool {
+        return match self.role {
+            Role::Admin => true,
+            Role::TeamManager => self.site_team_name.as_deref() == Some(site_team_name),
+            Role::Viewer => false,
+        };
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+    InsufficientRole,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiUser {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(token) = request.headers().get_one("Authorization").and_then(|value| value.strip_prefix("Bearer ")) else {
+            return Outcome::Error((Status::Unauthorized, AuthError::MissingToken));
+        };
+        let Some(db_pool) = request.rocket().state::<DbPool>() else {
+            return Outcome::Error((Status::InternalServerError, AuthError::InvalidToken));
+        };
+
+        let api_token = query_api_token(&db_pool.get().unwrap(), &hash_token(token.trim()));
+        let Some(api_token) = api_token else {
+            return Outcome::Error((Status::Unauthorized, AuthError::InvalidToken));
+        };
+        let Some(role) = Role::from_str(&api_token.role) else {
+            return Outcome::Error((Status::Unauthorized, AuthError::InvalidToken));
+        };
+
+        return Outcome::Success(ApiUser{
+            name: api_token.name,
+            role,
+            site_team_name: api_token.site_team_name,
+        });
+    }
+}
+
+async fn require_role(request: &Request<'_>, role: Role) -> Outcome<ApiUser, AuthError> {
+    let user = try_outcome!(request.guard::<ApiUser>().await);
+    if user.role < role {
+        return Outcome::Error((Status::Forbidden, AuthError::InsufficientRole));
+    }
+    return Outcome::Success(user);
+}
+
+pub struct TeamManagerUser(pub ApiUser);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TeamManagerUser {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        return require_role(request, Role::TeamManager).await.map(TeamManagerUser);
+    }
+}
+
+pub struct AdminUser(pub ApiUser);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        return require_role(request, Role::Admin).await.map(AdminUser);
+    }
+}
@@ -1,29 +1,35 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use std::{fs, path::PathBuf, path::Path, io::Write};
+use std::{fs, path::PathBuf, path::Path};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use r2d2_sqlite::SqliteConnectionManager;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use serde_json::{self, Value};
-use rusqlite::{self, named_params};
+use rusqlite::{self, named_params, OptionalExtension};
 use rusqlite::Connection;
 use chrono::{self, TimeZone};
-use zip::write::FileOptions;
 use lazy_static::lazy_static;
 use sea_query_rusqlite::RusqliteBinder;
 use sea_query::{
+    all,
     Query,
     Expr,
     Order,
     SqliteQueryBuilder,
-    Func
+    Func,
+    SimpleExpr
 };
 use crate::schema::{
-    is_event_type, is_main_event, is_simsession_type, Car, CarClass, CarClassResult, Driver, DriverResult, ReasonOut, SchemaUtils, Session, Simsession, SiteTeam, SiteTeamMember, SiteTeamTeam, Subsession, TrackConfig
+    is_category_type, is_event_type, is_main_event, is_official, is_simsession_type, Car, CarClass, CarClassResult, Driver, DriverResult, ReasonOut, SchemaUtils, Season, SeasonDriverStanding, SeasonSchedule, SeasonTeamStanding, Session, Simsession, SiteTeam, SiteTeamAlias, SiteTeamMember, SiteTeamTeam, Subsession, TrackConfig
 };
 use crate::event_type::EventType;
 use crate::category_type::CategoryType;
 use crate::driverid::DriverId;
 use crate::simsession_type::SimsessionType;
+use crate::session_archive;
 
 use crate::dirs::{
     get_base_dir,
@@ -31,10 +37,13 @@ use crate::dirs::{
 };
 
 const SESSIONS_DIR: &str = "data/sessions";
+const SESSION_PACKS_DIR: &str = "data/session-packs";
 const TRACK_DATA_FILE: &str = "data/tracks.json";
 const CAR_DATA_FILE: &str = "data/cars.json";
 const CAR_CLASS_DATA_FILE: &str = "data/car-classes.json";
 const SEASON_DATA_FILE: &str = "data/seasons.json";
+const SEASON_SCHEDULE_DATA_FILE: &str = "data/season-schedules.json";
+const MEMBER_STATS_DIR: &str = "data/member-stats";
 const SITE_TEAMS_DATA_FILE: &str = "static-data/site-teams.json";
 const SQLITE_DB_FILE: &str = "stats.db";
 
@@ -73,6 +82,13 @@ pub fn get_season_data_file() -> &'static Path {
     return FILE.as_path();
 }
 
+pub fn get_season_schedule_data_file() -> &'static Path {
+    lazy_static! {
+        static ref FILE: PathBuf = get_base_dir().join(SEASON_SCHEDULE_DATA_FILE);
+    }
+    return FILE.as_path();
+}
+
 pub fn get_sessions_dir() -> &'static Path {
     lazy_static! {
         static ref DIR: PathBuf = get_base_dir().join(SESSIONS_DIR);
@@ -80,6 +96,20 @@ pub fn get_sessions_dir() -> &'static Path {
     return DIR.as_path();
 }
 
+pub fn get_session_packs_dir() -> &'static Path {
+    lazy_static! {
+        static ref DIR: PathBuf = get_base_dir().join(SESSION_PACKS_DIR);
+    }
+    return DIR.as_path();
+}
+
+pub fn get_member_stats_dir() -> &'static Path {
+    lazy_static! {
+        static ref DIR: PathBuf = get_base_dir().join(MEMBER_STATS_DIR);
+    }
+    return DIR.as_path();
+}
+
 pub fn get_site_teams_data_file() -> &'static Path {
     lazy_static! {
         static ref FILE: PathBuf = get_static_dir().join(SITE_TEAMS_DATA_FILE);
@@ -111,10 +141,13 @@ pub struct DbContext<'a> {
     insert_car_class_result_statement: rusqlite::Statement<'a>,
     insert_driver_result_statement: rusqlite::Statement<'a>,
     insert_season_statement: rusqlite::Statement<'a>,
-    insert_site_team_statement: rusqlite::Statement<'a>,
-    insert_site_team_member_statement: rusqlite::Statement<'a>,
-    insert_site_team_team_statement: rusqlite::Statement<'a>,
+    insert_season_schedule_statement: rusqlite::Statement<'a>,
+    insert_season_driver_standing_statement: rusqlite::Statement<'a>,
+    insert_season_team_standing_statement: rusqlite::Statement<'a>,
     insert_reason_out_statement: rusqlite::Statement<'a>,
+    insert_member_profile_statement: rusqlite::Statement<'a>,
+    insert_member_career_stat_statement: rusqlite::Statement<'a>,
+    insert_member_yearly_stat_statement: rusqlite::Statement<'a>,
 }
 
 pub fn create_db_context<'a>(tx: &'a mut rusqlite::Transaction) -> DbContext<'a> {
@@ -228,28 +261,80 @@ pub fn create_db_context<'a>(tx: &'a mut rusqlite::Transaction) -> DbContext<'a>
             ?, /* fixed_setup */
             ?  /* driver_changes */
     );"#).unwrap();
-    let insert_site_team_statement = tx.prepare(r#"
-        INSERT INTO site_team VALUES(
-            ?, /* site_team_id */
-            ?, /* site_team_name */
-            ?, /* discord_hook_url */
-            ?  /* team_report_discord_hook_url */
+    let insert_season_schedule_statement = tx.prepare(r#"
+        INSERT OR REPLACE INTO season_schedule VALUES(
+            ?, /* season_id */
+            ?, /* series_id */
+            ?, /* race_week_num */
+            ?, /* track_id */
+            ?, /* start_date */
+            ?, /* race_lap_limit */
+            ?  /* race_time_limit */
     );"#).unwrap();
-    let insert_site_team_member_statement = tx.prepare(r#"
-        INSERT INTO site_team_member VALUES(
-            ?, /* site_team_id */
-            ?  /* cust_id */
+    let insert_season_driver_standing_statement = tx.prepare(r#"
+        INSERT INTO season_driver_standing VALUES(
+            ?, /* season_id */
+            ?, /* car_class_id */
+            ?, /* race_week_num */
+            ?, /* cust_id */
+            ?, /* division */
+            ?, /* position */
+            ?, /* points */
+            ?, /* starts */
+            ?  /* wins */
     );"#).unwrap();
-    let insert_site_team_team_statement = tx.prepare(r#"
-        INSERT INTO site_team_team VALUES(
-            ?, /* site_team_id */
-            ?  /* team_id */
+    let insert_season_team_standing_statement = tx.prepare(r#"
+        INSERT INTO season_team_standing VALUES(
+            ?, /* season_id */
+            ?, /* car_class_id */
+            ?, /* race_week_num */
+            ?, /* team_id */
+            ?, /* team_name */
+            ?, /* position */
+            ?  /* points */
     );"#).unwrap();
     let insert_reason_out_statement = tx.prepare(r#"
         INSERT OR IGNORE INTO reason_out VALUES(
             ?, /* reason_out_id */
             ?  /* reason_out */
     );"#).unwrap();
+    let insert_member_profile_statement = tx.prepare(r#"
+        INSERT INTO member_profile VALUES(
+            ?, /* cust_id */
+            ?, /* display_name */
+            ?, /* member_since */
+            ?, /* club_name */
+            ?  /* synced_at */
+    );"#).unwrap();
+    let insert_member_career_stat_statement = tx.prepare(r#"
+        INSERT INTO member_career_stat VALUES(
+            ?, /* cust_id */
+            ?, /* category_id */
+            ?, /* starts */
+            ?, /* wins */
+            ?, /* top5 */
+            ?, /* poles */
+            ?, /* laps */
+            ?, /* laps_led */
+            ?, /* avg_incidents */
+            ?, /* avg_start_position */
+            ?  /* avg_finish_position */
+    );"#).unwrap();
+    let insert_member_yearly_stat_statement = tx.prepare(r#"
+        INSERT INTO member_yearly_stat VALUES(
+            ?, /* cust_id */
+            ?, /* year */
+            ?, /* category_id */
+            ?, /* starts */
+            ?, /* wins */
+            ?, /* top5 */
+            ?, /* poles */
+            ?, /* laps */
+            ?, /* laps_led */
+            ?, /* avg_incidents */
+            ?, /* avg_start_position */
+            ?  /* avg_finish_position */
+    );"#).unwrap();
 
     return DbContext {
         insert_track_config_statement,
@@ -263,10 +348,13 @@ pub fn create_db_context<'a>(tx: &'a mut rusqlite::Transaction) -> DbContext<'a>
         insert_car_class_result_statement,
         insert_driver_result_statement,
         insert_season_statement,
-        insert_site_team_statement,
-        insert_site_team_member_statement,
-        insert_site_team_team_statement,
+        insert_season_schedule_statement,
+        insert_season_driver_standing_statement,
+        insert_season_team_standing_statement,
         insert_reason_out_statement,
+        insert_member_profile_statement,
+        insert_member_career_stat_statement,
+        insert_member_yearly_stat_statement,
     };
 }
 
@@ -295,17 +383,67 @@ fn read_json_zip(zip_file: &Path) -> Value {
     return data;
 }
 
-fn write_single_file_zip(zip_path: &Path, file_name: &str, content: &str) {
-    let file = fs::File::create(zip_path).unwrap();
-    let mut zip = zip::ZipWriter::new(&file);
-    zip.start_file(file_name, FileOptions::default()).unwrap();
-    write!(&mut zip, "{content}").unwrap();
-    zip.finish().unwrap();
-}
+// Stored as PRAGMA user_version, bump whenever schema.sql changes so a server
+// running against a db built by an older version can tell it needs a rebuild
+pub const SCHEMA_VERSION: i64 = 8;
 
 fn build_db_schema(tx: &rusqlite::Transaction) {
     let schema_sql = include_str!("schema.sql");
     tx.execute_batch(schema_sql).unwrap();
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION).unwrap();
+}
+
+pub fn query_schema_version(con: &Connection) -> rusqlite::Result<i64> {
+    return con.query_row("PRAGMA user_version", (), |row| row.get(0));
+}
+
+// Dbs built before the metadata table existed get it with their first write,
+// same definition as in schema.sql
+fn ensure_metadata_table(tx: &rusqlite::Transaction) {
+    tx.execute("CREATE TABLE IF NOT EXISTS metadata(key TEXT PRIMARY KEY NOT NULL, value INTEGER NOT NULL)", ()).unwrap();
+}
+
+fn set_metadata(tx: &rusqlite::Transaction, key: &str, value: i64) {
+    ensure_metadata_table(tx);
+    tx.execute("INSERT OR REPLACE INTO metadata(key, value) VALUES(?1, ?2)", (key, value)).unwrap();
+}
+
+// None if never set (or the db predates the metadata table)
+fn query_metadata(con: &Connection, key: &str) -> Option<i64> {
+    return con.query_row("SELECT value FROM metadata WHERE key = ?1", (key,), |row| row.get(0)).ok();
+}
+
+// Bumped by every write to the db, so anything derived from its contents
+// (ETags of the api responses) can tell when it might have changed
+const DATA_VERSION_KEY: &str = "data_version";
+const LAST_SYNC_TIME_KEY: &str = "last_sync_time";
+
+pub fn bump_data_version(tx: &rusqlite::Transaction) {
+    ensure_metadata_table(tx);
+    tx.execute(
+        "INSERT INTO metadata(key, value) VALUES(?1, 1) ON CONFLICT(key) DO UPDATE SET value = value + 1",
+        (DATA_VERSION_KEY,)).unwrap();
+}
+
+// A rebuilt db starts from the current time instead of 1, so versions handed out
+// before the rebuild are not reused for different contents
+fn init_data_version(tx: &rusqlite::Transaction) {
+    set_metadata(tx, DATA_VERSION_KEY, chrono::Utc::now().timestamp());
+}
+
+// 0 if the db was never written to
+pub fn query_data_version(con: &Connection) -> i64 {
+    return query_metadata(con, DATA_VERSION_KEY).unwrap_or(0);
+}
+
+// Set when a sync from the iRacing API finished
+pub fn set_last_sync_time(tx: &rusqlite::Transaction) {
+    set_metadata(tx, LAST_SYNC_TIME_KEY, chrono::Utc::now().timestamp());
+}
+
+// unix timestamp, None if the db was never synced
+pub fn query_last_sync_time(con: &Connection) -> Option<i64> {
+    return query_metadata(con, LAST_SYNC_TIME_KEY);
 }
 
 fn build_db_indices(tx: &rusqlite::Transaction) {
@@ -373,71 +511,91 @@ fn add_season_to_db(ctx: &mut DbContext, season: &Value) {
     )).unwrap();
 }
 
-fn add_site_team_member_list_to_db(
-    ctx: &mut DbContext,
-    id: usize,
-    name: &str,
-    discord_hook_url: Option<&str>,
-    team_report_discord_hook_url: Option<&str>,
-    members: &Vec<Value>,
-    teams: &Option<&Vec<Value>>)
-{
-    ctx.insert_site_team_statement.execute((
-        id,
-        name,
-        discord_hook_url,
-        team_report_discord_hook_url,
-    )).unwrap();
-
-    for member in members {
-        ctx.insert_site_team_member_statement.execute((
-            id,
-            member["cust_id"].as_i64().unwrap()
+fn add_season_schedule_to_db(ctx: &mut DbContext, season: &Value) {
+    for week in season["schedules"].as_array().unwrap() {
+        ctx.insert_season_schedule_statement.execute((
+            season["season_id"].as_i64().unwrap(),
+            season["series_id"].as_i64().unwrap(),
+            week["race_week_num"].as_i64().unwrap(),
+            week["track"]["track_id"].as_i64().unwrap(),
+            week["start_date"].as_str().unwrap(),
+            week["race_lap_limit"].as_i64(), // kept as optional to allow NULL inserts
+            week["race_time_limit"].as_i64(), // kept as optional to allow NULL inserts
         )).unwrap();
     }
+}
 
-    if let Some(teams) = *teams {
-        for team in teams {
-            ctx.insert_site_team_team_statement.execute((
-                id,
-                team["team_id"].as_i64().unwrap()
-            )).unwrap();
-        }
-    }
+fn add_season_driver_standing_to_db(ctx: &mut DbContext, season_id: i64, car_class_id: i64, race_week_num: i64, standing: &Value) {
+    ctx.insert_season_driver_standing_statement.execute((
+        season_id,
+        car_class_id,
+        race_week_num,
+        standing["cust_id"].as_i64().unwrap(),
+        standing["division"].as_i64().unwrap_or(-1),
+        standing["rank"].as_i64().unwrap(),
+        standing["points"].as_i64().unwrap(),
+        standing["starts"].as_i64().unwrap_or(0),
+        standing["wins"].as_i64().unwrap_or(0),
+    )).unwrap();
 }
 
-fn add_site_team_to_db(ctx: &mut DbContext, id: &mut usize, team: &Value) {
-    let members = team["members"].as_array().unwrap();
-    let teams = team["teams"].as_array();
-
-    add_site_team_member_list_to_db(
-        ctx,
-        *id,
-        team["name"].as_str().unwrap(), 
-        team["discord_hook_url"].as_str(), // kept as optional to allow NULL inserts
-        team["team_report_discord_hook_url"].as_str(), // kept as optional to allow NULL inserts
-        &members,
-        &teams
-    );
-
-    *id += 1;
-
-    let aliases = team["aliases"].as_array();
-    if let Some(aliases) = aliases {
-        // insert aliases without hook url
-        for alias in aliases {
-            add_site_team_member_list_to_db(
-                ctx,
-                *id,
-                alias.as_str().unwrap(), 
-                None,
-                None,
-                &members,
-                &teams
-            );
-        }
+fn add_season_team_standing_to_db(ctx: &mut DbContext, season_id: i64, car_class_id: i64, race_week_num: i64, standing: &Value) {
+    ctx.insert_season_team_standing_statement.execute((
+        season_id,
+        car_class_id,
+        race_week_num,
+        standing["team_id"].as_i64().unwrap(),
+        standing["team_name"].as_str().unwrap_or(""),
+        standing["rank"].as_i64().unwrap(),
+        standing["points"].as_i64().unwrap(),
+    )).unwrap();
+}
+
+// stats is a member stats cache file, see write_cached_member_stats_json
+fn add_member_stats_to_db(ctx: &mut DbContext, stats: &Value) {
+    let cust_id = stats["cust_id"].as_i64().unwrap();
+    let member_info = &stats["profile"]["member_info"];
+    ctx.insert_member_profile_statement.execute((
+        cust_id,
+        member_info["display_name"].as_str().unwrap(),
+        member_info["member_since"].as_str(),
+        member_info["club_name"].as_str(),
+        stats["synced_at"].as_str().unwrap(),
+    )).unwrap();
+
+    for stat in stats["career"]["stats"].as_array().unwrap() {
+        ctx.insert_member_career_stat_statement.execute((
+            cust_id,
+            stat["category_id"].as_i64().unwrap(),
+            stat["starts"].as_i64().unwrap(),
+            stat["wins"].as_i64().unwrap(),
+            stat["top5"].as_i64().unwrap(),
+            stat["poles"].as_i64().unwrap(),
+            stat["laps"].as_i64().unwrap(),
+            stat["laps_led"].as_i64().unwrap(),
+            stat["avg_incidents"].as_f64().unwrap(),
+            stat["avg_start_position"].as_f64().unwrap(),
+            stat["avg_finish_position"].as_f64().unwrap(),
+        )).unwrap();
+    }
 
-        *id += 1;
+    for stat in stats["yearly"]["stats"].as_array().unwrap() {
+        // the year is a string in some responses
+        let year = stat["year"].as_i64().or_else(|| stat["year"].as_str()?.parse().ok()).unwrap();
+        ctx.insert_member_yearly_stat_statement.execute((
+            cust_id,
+            year,
+            stat["category_id"].as_i64().unwrap(),
+            stat["starts"].as_i64().unwrap(),
+            stat["wins"].as_i64().unwrap(),
+            stat["top5"].as_i64().unwrap(),
+            stat["poles"].as_i64().unwrap(),
+            stat["laps"].as_i64().unwrap(),
+            stat["laps_led"].as_i64().unwrap(),
+            stat["avg_incidents"].as_f64().unwrap(),
+            stat["avg_start_position"].as_f64().unwrap(),
+            stat["avg_finish_position"].as_f64().unwrap(),
+        )).unwrap();
     }
 }
 
@@ -593,24 +751,89 @@ fn add_subsession_to_db(ctx: &mut DbContext, subsession: &Value) {
     }
 }
 
-fn add_sessions_to_db<I>(ctx: &mut DbContext, files: I) 
-    where I: Iterator<Item = PathBuf>
-{
-    let mut i = 0;
-    for session_file in files {
-        if session_file.extension().unwrap_or_default() != "zip" {
-            continue;
-        }
-        if i % 1000 == 0 {
-            println!("Progress: {}", i);
+struct RebuildProgress {
+    total: usize,
+    done: usize,
+    start: Instant,
+    last_report: Instant,
+}
+
+impl RebuildProgress {
+    fn new(total: usize) -> Self {
+        let now = Instant::now();
+        return RebuildProgress{
+            total,
+            done: 0,
+            start: now,
+            last_report: now,
+        };
+    }
+
+    fn advance(&mut self) {
+        self.done += 1;
+        if self.last_report.elapsed() >= Duration::from_secs(5) {
+            self.last_report = Instant::now();
+            self.report();
         }
-        i += 1;
+    }
 
-        let data = read_json_zip(session_file.as_path());
-        add_subsession_to_db(ctx, &data);
+    fn report(&self) {
+        let elapsed_secs = self.start.elapsed().as_secs_f64();
+        let rate = self.done as f64 / elapsed_secs.max(0.001);
+        let percent = if self.total == 0 { 100.0 } else { 100.0 * self.done as f64 / self.total as f64 };
+        let eta_secs = if rate > 0.0 { ((self.total - self.done) as f64 / rate) as u64 } else { 0 };
+
+        println!("Progress: {}/{} ({percent:.1}%) {rate:.0}/s, elapsed {}, ETA {}",
+            self.done,
+            self.total,
+            format_duration(elapsed_secs as u64),
+            format_duration(eta_secs)
+        );
     }
 }
 
+fn format_duration(secs: u64) -> String {
+    return format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60);
+}
+
+// Reading, unzipping and parsing cached sessions is spread across a worker pool,
+// while the inserts are done on the calling thread which owns the DbContext
+fn add_sessions_to_db(ctx: &mut DbContext, subsession_ids: Vec<i64>) {
+    let total = subsession_ids.len();
+    let worker_count = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    println!("Adding {total} sessions using {worker_count} reader threads");
+
+    let queue = Mutex::new(subsession_ids.into_iter());
+    let (sender, receiver) = mpsc::sync_channel::<Value>(worker_count * 16);
+
+    thread::scope(|scope| {
+        for _ in 0..worker_count {
+            let sender = sender.clone();
+            let queue = &queue;
+            scope.spawn(move || {
+                loop {
+                    let next = queue.lock().unwrap().next();
+                    let Some(subsession_id) = next else {
+                        break;
+                    };
+                    if sender.send(read_cached_session_json(subsession_id)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // only the workers hold senders now, so the loop below ends when they are all done
+        drop(sender);
+
+        let mut progress = RebuildProgress::new(total);
+        for data in receiver {
+            add_subsession_to_db(ctx, &data);
+            progress.advance();
+        }
+        progress.report();
+    });
+}
+
 fn add_reason_out_to_db(ctx: &mut DbContext, reason_out_id: i64, reason_out: &str) {
     ctx.insert_reason_out_statement.execute((
         reason_out_id,
@@ -643,12 +866,24 @@ pub fn rebuild_seasons(ctx: &mut DbContext) {
     for season in seasons.as_array().unwrap() {
         // This is a duplicated season_id :/
         if season["season_id"].as_i64().unwrap() == 4222 && season["season_year"].as_i64().unwrap() == 2023 {
-            return;
+            continue;
         }
         add_season_to_db(ctx, &season);
     }
 }
 
+pub fn rebuild_season_schedules(ctx: &mut DbContext) {
+    // schedules are synced separately, older setups may not have them yet
+    let Ok(contents) = fs::read_to_string(get_season_schedule_data_file()) else {
+        return;
+    };
+    let seasons: Value = serde_json::from_str(&contents).unwrap();
+
+    for season in seasons.as_array().unwrap() {
+        add_season_schedule_to_db(ctx, season);
+    }
+}
+
 pub fn rebuild_car_classes(ctx: &mut DbContext) {
     let contents = fs::read_to_string(get_car_class_data_file()).unwrap();
     let car_classes: Value = serde_json::from_str(&contents).unwrap();
@@ -658,32 +893,68 @@ pub fn rebuild_car_classes(ctx: &mut DbContext) {
     }
 }
 
-fn rebuild_sessions(ctx: &mut DbContext) {
-    let paths = fs::read_dir(get_sessions_dir()).unwrap();
-    add_sessions_to_db(ctx, paths.map(|e| e.unwrap().path()));
+pub fn rebuild_member_stats(ctx: &mut DbContext) {
+    // only exists once member stats were synced
+    let Ok(entries) = fs::read_dir(get_member_stats_dir()) else {
+        return;
+    };
+    for entry in entries {
+        let contents = fs::read_to_string(entry.unwrap().path()).unwrap();
+        add_member_stats_to_db(ctx, &serde_json::from_str(&contents).unwrap());
+    }
 }
 
-fn rebuild_site_teams(ctx: &mut DbContext) {
-    let contents = fs::read_to_string(get_site_teams_data_file()).unwrap();
-    let root: Value = serde_json::from_str(&contents).unwrap();
+fn rebuild_sessions(ctx: &mut DbContext) {
+    add_sessions_to_db(ctx, list_cached_session_ids());
+}
 
-    let mut site_team_id = 1;
-    for team in root["site_teams"].as_array().unwrap() {
-        add_site_team_to_db(ctx, &mut site_team_id, team);
+fn rebuild_site_teams(tx: &rusqlite::Transaction, site_teams: &Vec<SiteTeamDefinition>) {
+    for site_team in site_teams {
+        if let Err(error) = insert_site_team(tx, site_team) {
+            println!("Skipping site team {}: {error:?}", site_team.name);
+        }
     }
 }
 
 pub fn add_session_to_db_from_cache(ctx: &mut DbContext, subsession_id: i64) {
-    add_subsession_to_db(ctx, &read_json_zip(get_session_cache_path(subsession_id).as_path()));
+    add_subsession_to_db(ctx, &read_cached_session_json(subsession_id));
+}
+
+fn remove_subsession_from_db(tx: &rusqlite::Transaction, subsession_id: i64) {
+    tx.execute("DELETE FROM driver_result WHERE subsession_id = ?", (subsession_id,)).unwrap();
+    tx.execute("DELETE FROM car_class_result WHERE subsession_id = ?", (subsession_id,)).unwrap();
+    tx.execute("DELETE FROM simsession WHERE subsession_id = ?", (subsession_id,)).unwrap();
+    tx.execute("DELETE FROM subsession WHERE subsession_id = ?", (subsession_id,)).unwrap();
+}
+
+// Replaces all rows belonging to the given subsessions in a single transaction
+pub fn replace_sessions_in_db(subsessions: &Vec<Value>) {
+    let mut con = create_db_connection();
+    let mut tx = con.transaction().unwrap();
+    {
+        for subsession in subsessions {
+            remove_subsession_from_db(&tx, subsession["subsession_id"].as_i64().unwrap());
+        }
+
+        let mut ctx = create_db_context(&mut tx);
+        for subsession in subsessions {
+            add_subsession_to_db(&mut ctx, subsession);
+        }
+    }
+    bump_data_version(&tx);
+    tx.commit().unwrap();
 }
 
+// Sessions are read from the packed archive, falling back to the legacy one zip per subsession layout
 pub fn read_cached_session_json(subsession_id: i64) -> Value {
+    if let Some(contents) = session_archive::read(subsession_id) {
+        return serde_json::from_str(&contents).unwrap();
+    }
     return read_json_zip(get_session_cache_path(subsession_id).as_path());
 }
 
 pub fn write_cached_session_json(subsession_id: i64, json: &Value) {
-    let content = json.to_string();
-    write_single_file_zip(get_session_cache_path(subsession_id).as_path(), "session.json", &content);
+    session_archive::write(subsession_id, &json.to_string());
 }
 
 pub fn write_cached_car_infos_json(json: &Value) {
@@ -707,6 +978,26 @@ pub fn write_cached_track_infos_json(json: &Value) {
     ).unwrap();
 }
 
+pub fn write_cached_season_schedules_json(json: &Value) {
+    fs::write(
+        get_season_schedule_data_file(),
+        serde_json::to_string(&json).unwrap()
+    ).unwrap();
+}
+
+fn get_member_stats_cache_path(cust_id: i64) -> PathBuf {
+    return get_member_stats_dir().join(format!("{cust_id}.json"));
+}
+
+// json has the cust_id, synced_at and the profile, career and yearly responses
+pub fn write_cached_member_stats_json(cust_id: i64, json: &Value) {
+    fs::create_dir_all(get_member_stats_dir()).unwrap();
+    fs::write(
+        get_member_stats_cache_path(cust_id),
+        serde_json::to_string(&json).unwrap()
+    ).unwrap();
+}
+
 pub fn write_cached_seasons_json(json: &Value) {
     fs::write(
         get_season_data_file(),
@@ -719,7 +1010,65 @@ pub fn get_session_cache_path(subsession_id: i64) -> PathBuf {
 }
 
 pub fn is_session_cached(subsession_id: i64) -> bool {
-    return get_session_cache_path(subsession_id).exists();
+    return session_archive::contains(subsession_id) || get_session_cache_path(subsession_id).exists();
+}
+
+fn parse_legacy_session_file_name(path: &Path) -> Option<i64> {
+    let file_name = path.file_name()?.to_str()?;
+    return file_name.strip_suffix(".session.zip")?.parse::<i64>().ok();
+}
+
+fn list_legacy_cached_session_ids() -> Vec<i64> {
+    let mut subsession_ids = Vec::new();
+    for entry in fs::read_dir(get_sessions_dir()).unwrap() {
+        if let Some(subsession_id) = parse_legacy_session_file_name(entry.unwrap().path().as_path()) {
+            subsession_ids.push(subsession_id);
+        }
+    }
+    return subsession_ids;
+}
+
+pub fn list_cached_session_ids() -> Vec<i64> {
+    let mut subsession_ids = session_archive::list_subsession_ids();
+    let packed: HashSet<i64> = subsession_ids.iter().copied().collect();
+
+    for subsession_id in list_legacy_cached_session_ids() {
+        if !packed.contains(&subsession_id) {
+            subsession_ids.push(subsession_id);
+        }
+    }
+    return subsession_ids;
+}
+
+// One-time conversion from one zip per subsession to the packed archive.
+// Legacy files are deleted once their packed copy reads back identical.
+pub fn convert_session_cache_to_archive() {
+    let subsession_ids = list_legacy_cached_session_ids();
+    let len = subsession_ids.len();
+    println!("Converting {len} cached sessions");
+
+    for (i, subsession_id) in subsession_ids.into_iter().enumerate() {
+        if i % 1000 == 0 {
+            println!("Progress: {i}/{len}");
+        }
+
+        let zip_path = get_session_cache_path(subsession_id);
+        let contents = read_single_file_zip(zip_path.as_path());
+        if contents.is_empty() {
+            println!("Skipping malformed {}", zip_path.display());
+            continue;
+        }
+
+        if session_archive::read(subsession_id).as_ref() != Some(&contents) {
+            session_archive::write(subsession_id, &contents);
+        }
+
+        if session_archive::read(subsession_id).as_ref() == Some(&contents) {
+            fs::remove_file(zip_path).unwrap();
+        } else {
+            println!("Failed to verify packed copy of {subsession_id}, keeping {}", zip_path.display());
+        }
+    }
 }
 
 pub struct DriverSession {
@@ -747,8 +1096,41 @@ pub struct DriverSession {
     pub season_quarter: i32,
 }
 
-pub fn query_driver_sessions(con: &Connection, driver_id: &DriverId) -> Option<Vec<DriverSession>> {
-    let (sql, params) = Query::select()
+#[derive(Default)]
+pub struct DriverSessionFilter {
+    // start_time >= since
+    pub since: Option<String>,
+    // start_time < until
+    pub until: Option<String>,
+    pub category: Option<CategoryType>,
+    pub event_type: Option<EventType>,
+    pub official: Option<bool>,
+    pub car_id: Option<i64>,
+    pub track_id: Option<i64>,
+}
+
+// Position of a session in the (start_time, subsession_id, simsession_number) ordering
+pub struct DriverSessionCursor {
+    pub start_time: String,
+    pub subsession_id: i64,
+    pub simsession_number: i32,
+}
+
+#[derive(Default)]
+pub struct DriverSessionPage {
+    pub descending: bool,
+    // only sessions after this one (in the requested order) are returned
+    pub after: Option<DriverSessionCursor>,
+    pub limit: Option<u64>,
+}
+
+pub fn query_driver_sessions(
+    con: &Connection,
+    driver_id: &DriverId,
+    filter: &DriverSessionFilter,
+    page: &DriverSessionPage) -> Option<Vec<DriverSession>>
+{
+    let mut query = Query::select()
         .column((DriverResult::Table, DriverResult::SubsessionId))
         .column((DriverResult::Table, DriverResult::OldiRating))
         .column((DriverResult::Table, DriverResult::NewiRating))
@@ -777,7 +1159,59 @@ pub fn query_driver_sessions(con: &Connection, driver_id: &DriverId) -> Option<V
         .join_subsession_to_session()
         .join_subsession_to_track_config()
         .match_driver_id(driver_id, false)
-        .build_rusqlite(SqliteQueryBuilder);
+        .to_owned();
+
+    if let Some(since) = &filter.since {
+        query.and_where(Expr::col((Subsession::Table, Subsession::StartTime)).gte(since.clone()));
+    }
+    if let Some(until) = &filter.until {
+        query.and_where(Expr::col((Subsession::Table, Subsession::StartTime)).lt(until.clone()));
+    }
+    if let Some(category) = filter.category {
+        query.and_where(is_category_type(category));
+    }
+    if let Some(event_type) = filter.event_type {
+        query.and_where(is_event_type(event_type));
+    }
+    if let Some(official) = filter.official {
+        query.and_where(Expr::col((Subsession::Table, Subsession::OfficialSession)).eq(official));
+    }
+    if let Some(car_id) = filter.car_id {
+        query.and_where(Expr::col((DriverResult::Table, DriverResult::CarId)).eq(car_id));
+    }
+    if let Some(track_id) = filter.track_id {
+        query.and_where(Expr::col((TrackConfig::Table, TrackConfig::TrackId)).eq(track_id));
+    }
+
+    if let Some(after) = &page.after {
+        let sort_key = Expr::tuple([
+            Expr::col((Subsession::Table, Subsession::StartTime)).into(),
+            Expr::col((DriverResult::Table, DriverResult::SubsessionId)).into(),
+            Expr::col((DriverResult::Table, DriverResult::SimsessionNumber)).into(),
+        ]);
+        let after_key = Expr::tuple([
+            Expr::val(after.start_time.clone()).into(),
+            Expr::val(after.subsession_id).into(),
+            Expr::val(after.simsession_number).into(),
+        ]);
+        if page.descending {
+            query.and_where(sort_key.lt(after_key));
+        } else {
+            query.and_where(sort_key.gt(after_key));
+        }
+    }
+
+    let order = if page.descending { Order::Desc } else { Order::Asc };
+    query
+        .order_by((Subsession::Table, Subsession::StartTime), order.clone())
+        .order_by((DriverResult::Table, DriverResult::SubsessionId), order.clone())
+        .order_by((DriverResult::Table, DriverResult::SimsessionNumber), order);
+
+    if let Some(limit) = page.limit {
+        query.limit(limit);
+    }
+
+    let (sql, params) = query.build_rusqlite(SqliteQueryBuilder);
 
     let mut stmt = con.prepare(sql.as_str()).unwrap();
     let mut rows = stmt.query(&*params.as_params()).unwrap();
@@ -933,6 +1367,137 @@ pub fn query_customer_names(con: &Connection, cust_ids: Vec<i64>) -> Vec<Custome
     return values;
 }
 
+// The format of static-data/site-teams.json, also used by the site team api
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct SiteTeamDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discord_hook_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team_report_discord_hook_url: Option<String>,
+    #[serde(default)]
+    pub members: Vec<SiteTeamMemberDefinition>,
+    #[serde(default)]
+    pub teams: Vec<SiteTeamTeamDefinition>,
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct SiteTeamMemberDefinition {
+    pub cust_id: i64,
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct SiteTeamTeamDefinition {
+    pub team_id: i64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SiteTeamsFile {
+    pub site_teams: Vec<SiteTeamDefinition>,
+}
+
+#[derive(Debug)]
+pub enum SiteTeamError {
+    NotFound,
+    // the name or alias is already used by a site team
+    NameTaken(String),
+}
+
+// Matches the site team called `name`, or having `name` as an alias
+fn site_team_name_matches(name: &str) -> SimpleExpr {
+    return Expr::cust_with_values(
+        r#""site_team"."site_team_id" IN (SELECT site_team_id FROM site_team WHERE site_team_name = ? UNION SELECT site_team_id FROM site_team_alias WHERE alias = ?)"#,
+        [name, name]);
+}
+
+pub fn read_site_teams_data_file() -> Vec<SiteTeamDefinition> {
+    let contents = fs::read_to_string(get_site_teams_data_file()).unwrap();
+    let file: SiteTeamsFile = serde_json::from_str(&contents).unwrap();
+    return file.site_teams;
+}
+
+fn try_query_site_team_definitions(con: &Connection) -> rusqlite::Result<Vec<SiteTeamDefinition>> {
+    let mut site_teams = Vec::new();
+    let mut site_team_ids = HashMap::new();
+    {
+        let (sql, params) = Query::select()
+            .column(SiteTeam::SiteTeamId)
+            .column(SiteTeam::SiteTeamName)
+            .column(SiteTeam::DiscordHookUrl)
+            .column(SiteTeam::TeamReportDiscordHookUrl)
+            .from(SiteTeam::Table)
+            .order_by(SiteTeam::SiteTeamName, Order::Asc)
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let mut stmt = con.prepare(sql.as_str())?;
+        let mut rows = stmt.query(&*params.as_params())?;
+        while let Some(row) = rows.next()? {
+            let site_team_id: i64 = row.get(0)?;
+            site_team_ids.insert(site_team_id, site_teams.len());
+            site_teams.push(SiteTeamDefinition{
+                name: row.get(1)?,
+                aliases: Vec::new(),
+                discord_hook_url: row.get(2)?,
+                team_report_discord_hook_url: row.get(3)?,
+                members: Vec::new(),
+                teams: Vec::new(),
+            });
+        }
+    }
+
+    let (sql, params) = Query::select()
+        .column(SiteTeamAlias::SiteTeamId)
+        .column(SiteTeamAlias::Alias)
+        .from(SiteTeamAlias::Table)
+        .order_by(SiteTeamAlias::Alias, Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+    let mut stmt = con.prepare(sql.as_str())?;
+    let mut rows = stmt.query(&*params.as_params())?;
+    while let Some(row) = rows.next()? {
+        let site_team_id: i64 = row.get(0)?;
+        site_teams[site_team_ids[&site_team_id]].aliases.push(row.get(1)?);
+    }
+
+    let (sql, params) = Query::select()
+        .column(SiteTeamMember::SiteTeamId)
+        .column(SiteTeamMember::CustId)
+        .from(SiteTeamMember::Table)
+        .order_by(SiteTeamMember::CustId, Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+    let mut stmt = con.prepare(sql.as_str())?;
+    let mut rows = stmt.query(&*params.as_params())?;
+    while let Some(row) = rows.next()? {
+        let site_team_id: i64 = row.get(0)?;
+        site_teams[site_team_ids[&site_team_id]].members.push(SiteTeamMemberDefinition{ cust_id: row.get(1)? });
+    }
+
+    let (sql, params) = Query::select()
+        .column(SiteTeamTeam::SiteTeamId)
+        .column(SiteTeamTeam::TeamId)
+        .from(SiteTeamTeam::Table)
+        .order_by(SiteTeamTeam::TeamId, Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+    let mut stmt = con.prepare(sql.as_str())?;
+    let mut rows = stmt.query(&*params.as_params())?;
+    while let Some(row) = rows.next()? {
+        let site_team_id: i64 = row.get(0)?;
+        site_teams[site_team_ids[&site_team_id]].teams.push(SiteTeamTeamDefinition{ team_id: row.get(1)? });
+    }
+
+    return Ok(site_teams);
+}
+
+pub fn query_site_team_definitions(con: &Connection) -> Vec<SiteTeamDefinition> {
+    return try_query_site_team_definitions(con).unwrap();
+}
+
+// Looked up by the site team name only, aliases are not considered
+pub fn query_site_team_definition(con: &Connection, name: &str) -> Option<SiteTeamDefinition> {
+    return query_site_team_definitions(con).into_iter().find(|site_team| site_team.name == name);
+}
+
 pub fn query_site_team_members(con: &Connection, team: &String) -> Vec<CustomerName> {
     let (sql, params) = Query::select()
         .column((Driver::Table, Driver::DisplayName))
@@ -940,7 +1505,7 @@ pub fn query_site_team_members(con: &Connection, team: &String) -> Vec<CustomerN
         .from(SiteTeam::Table)
         .join_site_team_member_to_driver()
         .join_site_team_to_site_team_member()
-        .and_where(Expr::col(SiteTeam::SiteTeamName).eq(team))
+        .and_where(site_team_name_matches(team))
         .build_rusqlite(SqliteQueryBuilder);
 
     // select display_name, site_team_member.cust_id
@@ -1079,6 +1644,44 @@ pub fn query_all_site_team_members(con: &Connection) -> Vec<i64> {
     return cust_ids;
 }
 
+// (season_year, season_quarter) of the newest synced season
+pub fn query_latest_season(con: &Connection) -> Option<(i32, i32)> {
+    let (sql, params) = Query::select()
+        .column((Season::Table, Season::SeasonYear))
+        .column((Season::Table, Season::SeasonQuarter))
+        .from(Season::Table)
+        .order_by((Season::Table, Season::SeasonYear), Order::Desc)
+        .order_by((Season::Table, Season::SeasonQuarter), Order::Desc)
+        .limit(1)
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    if let Some(row) = rows.next().unwrap() {
+        return Some((row.get(0).unwrap(), row.get(1).unwrap()));
+    }
+    return None;
+}
+
+pub fn query_subsession_ids_since(con: &Connection, start_date: String) -> Vec<i64> {
+    let (sql, params) = Query::select()
+        .column((Subsession::Table, Subsession::SubsessionId))
+        .from(Subsession::Table)
+        .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).gte(start_date))
+        .order_by((Subsession::Table, Subsession::SubsessionId), Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut subsession_ids = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        subsession_ids.push(row.get(0).unwrap());
+    }
+    return subsession_ids;
+}
+
 #[derive(Clone, Debug)]
 pub struct DiscordRaceResultReport {
     pub subsession_id: i64,
@@ -1425,7 +2028,7 @@ pub fn query_session_result(con: &Connection, subsession_ids: Vec<i64>, site_tea
         .and_where(Expr::col((DriverResult::Table, DriverResult::SubsessionId)).is_in(subsession_ids))
         .and_where(is_main_event())
         .and_where(is_event_type(EventType::Race))
-        .and_where(Expr::col((SiteTeam::Table, SiteTeam::SiteTeamName)).eq(site_team_name))
+        .and_where(site_team_name_matches(&site_team_name))
         .and_where(Expr::col((DriverResult::Table, DriverResult::LapsComplete)).gt(0))
         .order_by((Subsession::Table, Subsession::SubsessionId), Order::Asc)
         .order_by((DriverResult::Table, DriverResult::TeamId), Order::Asc)
@@ -1457,8 +2060,87 @@ pub fn query_session_result(con: &Connection, subsession_ids: Vec<i64>, site_tea
     return result;
 }
 
-pub struct SiteTeamDriverReport {
-    pub display_name: String,
+pub struct SiteTeamRace {
+    pub subsession_id: i64,
+    pub cust_id: i64,
+    pub driver_name: String,
+    pub series_name: String,
+    pub session_name: String,
+    pub start_time: chrono::DateTime<chrono::Utc>,
+    pub track_name: String,
+    pub config_name: String,
+    pub car_name: String,
+    pub finish_position_in_class: i64,
+    pub entries_in_class: i64,
+    pub reason_out: String,
+    pub laps_complete: i64,
+    pub average_lap: i64,
+}
+
+// Main event race results of every site team member since start_date
+pub fn query_site_team_races(con: &Connection, site_team_name: &String, start_date: String) -> Vec<SiteTeamRace> {
+    let (sql, params) = Query::select()
+        .column((Subsession::Table, Subsession::SubsessionId))
+        .column((DriverResult::Table, DriverResult::CustId))
+        .column((Driver::Table, Driver::DisplayName))
+        .column((Session::Table, Session::SeriesName))
+        .column((Session::Table, Session::SessionName))
+        .column((Subsession::Table, Subsession::StartTime))
+        .column((TrackConfig::Table, TrackConfig::TrackName))
+        .column((TrackConfig::Table, TrackConfig::ConfigName))
+        .column((Car::Table, Car::CarName))
+        .column((DriverResult::Table, DriverResult::FinishPositionInClass))
+        .column((CarClassResult::Table, CarClassResult::EntriesInClass))
+        .column((ReasonOut::Table, ReasonOut::ReasonOut))
+        .column((DriverResult::Table, DriverResult::LapsComplete))
+        .column((DriverResult::Table, DriverResult::AverageLap))
+        .from(DriverResult::Table)
+        .join_driver_result_to_subsession()
+        .join_driver_result_to_simsession()
+        .join_driver_result_to_driver()
+        .join_driver_result_to_car()
+        .join_driver_result_to_reason_out()
+        .join_driver_result_to_car_class_result()
+        .join_subsession_to_session()
+        .join_subsession_to_track_config()
+        .join_driver_to_site_team_member()
+        .join_site_team_member_to_site_team()
+        .and_where(site_team_name_matches(site_team_name))
+        .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).gte(start_date))
+        .and_where(is_main_event())
+        .and_where(is_event_type(EventType::Race))
+        .and_where(Expr::col((DriverResult::Table, DriverResult::LapsComplete)).gt(0))
+        .order_by((Subsession::Table, Subsession::StartTime), Order::Asc)
+        .order_by((DriverResult::Table, DriverResult::CustId), Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        result.push(SiteTeamRace{
+            subsession_id: row.get(0).unwrap(),
+            cust_id: row.get(1).unwrap(),
+            driver_name: row.get(2).unwrap(),
+            series_name: row.get(3).unwrap(),
+            session_name: row.get(4).unwrap_or(String::new()),
+            start_time: row.get(5).unwrap(),
+            track_name: row.get(6).unwrap(),
+            config_name: row.get(7).unwrap(),
+            car_name: row.get(8).unwrap(),
+            finish_position_in_class: row.get(9).unwrap(),
+            entries_in_class: row.get(10).unwrap(),
+            reason_out: row.get(11).unwrap(),
+            laps_complete: row.get(12).unwrap(),
+            average_lap: row.get(13).unwrap(),
+        });
+    }
+    return result;
+}
+
+pub struct SiteTeamDriverReport {
+    pub display_name: String,
     pub laps_complete: i64,
     pub incidents: i64,
     pub time_on_track: i64,
@@ -1492,7 +2174,7 @@ pub fn query_site_team_report(
             .join_subsession_to_track_config()
             .join_driver_to_site_team_member()
             .join_site_team_member_to_site_team()
-            .and_where(Expr::col((SiteTeam::Table, SiteTeam::SiteTeamName)).eq(&site_team_name))
+            .and_where(site_team_name_matches(&site_team_name))
             .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).gte(&start_date))
             .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).lt(&end_date))
             .group_by_col((Driver::Table, Driver::DisplayName))
@@ -1528,7 +2210,7 @@ pub fn query_site_team_report(
             .join_subsession_to_track_config()
             .join_driver_to_site_team_member()
             .join_site_team_member_to_site_team()
-            .and_where(Expr::col((SiteTeam::Table, SiteTeam::SiteTeamName)).eq(&site_team_name))
+            .and_where(site_team_name_matches(&site_team_name))
             .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).gte(&start_date))
             .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).lt(&end_date))
             .and_where(is_event_type(EventType::Race))
@@ -1570,7 +2252,7 @@ pub fn query_site_team_report(
                 INNER JOIN "site_team_member" ON "driver"."cust_id" = "site_team_member"."cust_id"
                 INNER JOIN "site_team" ON "site_team"."site_team_id" = "site_team_member"."site_team_id"
                 WHERE
-                    "site_team"."site_team_name" = :site_team_name AND
+                    "site_team"."site_team_id" IN (SELECT site_team_id FROM site_team WHERE site_team_name = :site_team_name UNION SELECT site_team_id FROM site_team_alias WHERE alias = :site_team_name) AND
                     "driver_result"."newi_rating" <> -1 AND
                     "driver_result"."oldi_rating" <> -1 AND
                     "subsession"."start_time" >= :start_date AND
@@ -1599,7 +2281,7 @@ pub fn query_site_team_report(
                 INNER JOIN "site_team_member" ON "driver"."cust_id" = "site_team_member"."cust_id"
                 INNER JOIN "site_team" ON "site_team"."site_team_id" = "site_team_member"."site_team_id"
                 WHERE
-                    "site_team"."site_team_name" = :site_team_name AND
+                    "site_team"."site_team_id" IN (SELECT site_team_id FROM site_team WHERE site_team_name = :site_team_name UNION SELECT site_team_id FROM site_team_alias WHERE alias = :site_team_name) AND
                     "driver_result"."newi_rating" <> -1 AND
                     "driver_result"."oldi_rating" <> -1 AND
                     "subsession"."start_time" >= :start_date AND
@@ -1693,7 +2375,7 @@ pub fn query_site_team_driver_pairings(
         JOIN site_team ON
             site_team.site_team_id = site_team_member.site_team_id
         WHERE
-            site_team_name = :site_team_name AND
+            site_team.site_team_id IN (SELECT site_team_id FROM site_team WHERE site_team_name = :site_team_name UNION SELECT site_team_id FROM site_team_alias WHERE alias = :site_team_name) AND
             simsession.simsession_type = 6 AND
             driver_result.team_id != 0
         GROUP BY
@@ -1748,12 +2430,12 @@ pub fn query_site_team_driver_pairings(
     return result;
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct DriverContentUsage {
     // track name -> on track time
-    track_map: HashMap<String, i64>,
+    pub track_map: HashMap<String, i64>,
     // car name -> on track time
-    car_map: HashMap<String, i64>
+    pub car_map: HashMap<String, i64>
 }
 
 impl DriverContentUsage {
@@ -1765,10 +2447,10 @@ impl DriverContentUsage {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct SiteTeamContentUsage {
     // driver name -> track usage map
-    driver_map: HashMap<String, DriverContentUsage>
+    pub driver_map: HashMap<String, DriverContentUsage>
 }
 
 pub fn query_site_team_content_usage(
@@ -1790,7 +2472,7 @@ pub fn query_site_team_content_usage(
             .join_subsession_to_track_config()
             .join_driver_to_site_team_member()
             .join_site_team_member_to_site_team()
-            .and_where(Expr::col((SiteTeam::Table, SiteTeam::SiteTeamName)).eq(&site_team_name))
+            .and_where(site_team_name_matches(&site_team_name))
             .group_by_col((Driver::Table, Driver::CustId))
             .group_by_col((TrackConfig::Table, TrackConfig::PackageId))
             .build_rusqlite(SqliteQueryBuilder);
@@ -1817,7 +2499,7 @@ pub fn query_site_team_content_usage(
             .join_driver_result_to_car()
             .join_driver_to_site_team_member()
             .join_site_team_member_to_site_team()
-            .and_where(Expr::col((SiteTeam::Table, SiteTeam::SiteTeamName)).eq(&site_team_name))
+            .and_where(site_team_name_matches(&site_team_name))
             .group_by_col((Driver::Table, Driver::CustId))
             .group_by_col((Car::Table, Car::CarId))
             .build_rusqlite(SqliteQueryBuilder);
@@ -1838,14 +2520,241 @@ pub fn query_site_team_content_usage(
     return result;
 }
 
+pub struct SiteTeamSeries {
+    pub series_id: i64,
+    pub series_name: String,
+    pub race_count: i64,
+    pub driver_count: i64,
+}
+
+// Official series the members of a site team raced in since start_date, most raced first
+pub fn query_site_team_series(
+    con: &Connection,
+    site_team_name: &String,
+    start_date: String) -> Vec<SiteTeamSeries>
+{
+    let (sql, params) = Query::select()
+        .column((Session::Table, Session::SeriesId))
+        .expr(Func::max(Expr::col((Session::Table, Session::SeriesName))))
+        .expr(Expr::cust(r#"COUNT(DISTINCT "driver_result"."subsession_id")"#))
+        .expr(Expr::cust(r#"COUNT(DISTINCT "driver_result"."cust_id")"#))
+        .from(DriverResult::Table)
+        .join_driver_result_to_subsession()
+        .join_driver_result_to_simsession()
+        .join_driver_result_to_driver()
+        .join_subsession_to_session()
+        .join_driver_to_site_team_member()
+        .join_site_team_member_to_site_team()
+        .and_where(site_team_name_matches(site_team_name))
+        .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).gte(start_date))
+        .and_where(is_event_type(EventType::Race))
+        .and_where(is_main_event())
+        .and_where(is_official())
+        .group_by_col((Session::Table, Session::SeriesId))
+        .order_by_expr(Expr::cust(r#"COUNT(DISTINCT "driver_result"."subsession_id")"#), Order::Desc)
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        result.push(SiteTeamSeries{
+            series_id: row.get(0).unwrap(),
+            series_name: row.get(1).unwrap(),
+            race_count: row.get(2).unwrap(),
+            driver_count: row.get(3).unwrap(),
+        });
+    }
+    return result;
+}
+
+pub struct ScheduleWeek {
+    pub season_id: i64,
+    pub series_id: i64,
+    pub race_week_num: i64,
+    pub track_id: i64,
+    pub track_name: String,
+    pub config_name: String,
+    pub start_date: chrono::NaiveDate,
+    pub race_lap_limit: Option<i64>,
+    pub race_time_limit: Option<i64>,
+}
+
+// Race weeks of the given series starting on or after start_date, in chronological order
+pub fn query_season_schedules(con: &Connection, series_ids: Vec<i64>, start_date: String) -> Vec<ScheduleWeek> {
+    let (sql, params) = Query::select()
+        .column((SeasonSchedule::Table, SeasonSchedule::SeasonId))
+        .column((SeasonSchedule::Table, SeasonSchedule::SeriesId))
+        .column((SeasonSchedule::Table, SeasonSchedule::RaceWeekNum))
+        .column((SeasonSchedule::Table, SeasonSchedule::TrackId))
+        .column((TrackConfig::Table, TrackConfig::TrackName))
+        .column((TrackConfig::Table, TrackConfig::ConfigName))
+        .column((SeasonSchedule::Table, SeasonSchedule::StartDate))
+        .column((SeasonSchedule::Table, SeasonSchedule::RaceLapLimit))
+        .column((SeasonSchedule::Table, SeasonSchedule::RaceTimeLimit))
+        .from(SeasonSchedule::Table)
+        // tracks may not be synced yet
+        .left_join(TrackConfig::Table,
+            Expr::col((TrackConfig::Table, TrackConfig::TrackId)).equals((SeasonSchedule::Table, SeasonSchedule::TrackId)))
+        .and_where(Expr::col((SeasonSchedule::Table, SeasonSchedule::SeriesId)).is_in(series_ids))
+        .and_where(Expr::col((SeasonSchedule::Table, SeasonSchedule::StartDate)).gte(start_date))
+        .order_by((SeasonSchedule::Table, SeasonSchedule::StartDate), Order::Asc)
+        .order_by((SeasonSchedule::Table, SeasonSchedule::SeriesId), Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        let start_date: String = row.get(6).unwrap();
+        result.push(ScheduleWeek{
+            season_id: row.get(0).unwrap(),
+            series_id: row.get(1).unwrap(),
+            race_week_num: row.get(2).unwrap(),
+            track_id: row.get(3).unwrap(),
+            track_name: row.get(4).unwrap_or_default(),
+            config_name: row.get(5).unwrap_or_default(),
+            start_date: chrono::NaiveDate::parse_from_str(&start_date, "%Y-%m-%d").unwrap(),
+            race_lap_limit: row.get(7).unwrap(),
+            race_time_limit: row.get(8).unwrap(),
+        });
+    }
+    return result;
+}
+
+// (season_id, car_class_id) pairs of official races site team members did since start_date
+pub fn query_site_team_season_car_classes(con: &Connection, start_date: String) -> Vec<(i64, i64)> {
+    let (sql, params) = Query::select()
+        .distinct()
+        .column((Season::Table, Season::SeasonId))
+        .column((DriverResult::Table, DriverResult::CarClassId))
+        .from(DriverResult::Table)
+        .join_driver_result_to_subsession()
+        .join_subsession_to_session()
+        .inner_join(Season::Table, all![
+            Expr::col((Season::Table, Season::SeriesId)).equals((Session::Table, Session::SeriesId)),
+            Expr::col((Season::Table, Season::SeasonYear)).equals((Session::Table, Session::SeasonYear)),
+            Expr::col((Season::Table, Season::SeasonQuarter)).equals((Session::Table, Session::SeasonQuarter)),
+        ])
+        .inner_join(SiteTeamMember::Table,
+            Expr::col((SiteTeamMember::Table, SiteTeamMember::CustId)).equals((DriverResult::Table, DriverResult::CustId)))
+        .and_where(Expr::col((Subsession::Table, Subsession::StartTime)).gte(start_date))
+        .and_where(is_official())
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        result.push((row.get(0).unwrap(), row.get(1).unwrap()));
+    }
+    return result;
+}
+
+pub struct DriverStandingWeek {
+    pub cust_id: i64,
+    pub display_name: String,
+    pub race_week_num: i64,
+    pub division: i64,
+    pub position: i64,
+    pub points: i64,
+    pub starts: i64,
+    pub wins: i64,
+}
+
+pub fn query_site_team_standings_history(
+    con: &Connection,
+    site_team_name: &String,
+    season_id: i64,
+    car_class_id: i64) -> Vec<DriverStandingWeek>
+{
+    let (sql, params) = Query::select()
+        .column((SeasonDriverStanding::Table, SeasonDriverStanding::CustId))
+        .column((Driver::Table, Driver::DisplayName))
+        .column((SeasonDriverStanding::Table, SeasonDriverStanding::RaceWeekNum))
+        .column((SeasonDriverStanding::Table, SeasonDriverStanding::Division))
+        .column((SeasonDriverStanding::Table, SeasonDriverStanding::Position))
+        .column((SeasonDriverStanding::Table, SeasonDriverStanding::Points))
+        .column((SeasonDriverStanding::Table, SeasonDriverStanding::Starts))
+        .column((SeasonDriverStanding::Table, SeasonDriverStanding::Wins))
+        .from(SeasonDriverStanding::Table)
+        .inner_join(Driver::Table,
+            Expr::col((SeasonDriverStanding::Table, SeasonDriverStanding::CustId)).equals((Driver::Table, Driver::CustId)))
+        .join_driver_to_site_team_member()
+        .join_site_team_member_to_site_team()
+        .and_where(site_team_name_matches(site_team_name))
+        .and_where(Expr::col((SeasonDriverStanding::Table, SeasonDriverStanding::SeasonId)).eq(season_id))
+        .and_where(Expr::col((SeasonDriverStanding::Table, SeasonDriverStanding::CarClassId)).eq(car_class_id))
+        .order_by((SeasonDriverStanding::Table, SeasonDriverStanding::CustId), Order::Asc)
+        .order_by((SeasonDriverStanding::Table, SeasonDriverStanding::RaceWeekNum), Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        result.push(DriverStandingWeek{
+            cust_id: row.get(0).unwrap(),
+            display_name: row.get(1).unwrap(),
+            race_week_num: row.get(2).unwrap(),
+            division: row.get(3).unwrap(),
+            position: row.get(4).unwrap(),
+            points: row.get(5).unwrap(),
+            starts: row.get(6).unwrap(),
+            wins: row.get(7).unwrap(),
+        });
+    }
+    return result;
+}
+
+pub struct TeamStandingWeek {
+    pub race_week_num: i64,
+    pub team_id: i64,
+    pub points: i64,
+}
+
+pub fn query_season_team_standings(con: &Connection, season_id: i64, car_class_id: i64) -> Vec<TeamStandingWeek> {
+    let (sql, params) = Query::select()
+        .column((SeasonTeamStanding::Table, SeasonTeamStanding::RaceWeekNum))
+        .column((SeasonTeamStanding::Table, SeasonTeamStanding::TeamId))
+        .column((SeasonTeamStanding::Table, SeasonTeamStanding::Points))
+        .from(SeasonTeamStanding::Table)
+        .and_where(Expr::col((SeasonTeamStanding::Table, SeasonTeamStanding::SeasonId)).eq(season_id))
+        .and_where(Expr::col((SeasonTeamStanding::Table, SeasonTeamStanding::CarClassId)).eq(car_class_id))
+        .order_by((SeasonTeamStanding::Table, SeasonTeamStanding::RaceWeekNum), Order::Asc)
+        .build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        result.push(TeamStandingWeek{
+            race_week_num: row.get(0).unwrap(),
+            team_id: row.get(1).unwrap(),
+            points: row.get(2).unwrap(),
+        });
+    }
+    return result;
+}
+
 pub fn rebuild_db_schema() {
+    // the sync states say how far the sessions in the db go, and there are none after this
+    let carried_over = CarriedOver{ driver_sync_states: Vec::new(), ..read_carried_over_for_rebuild() };
+
     fs::remove_file(get_sqlite_db_file()).ok(); // ignore error
 
     let mut con = create_db_connection();
     let tx = con.transaction().unwrap();
 
     build_db_schema(&tx);
+    insert_carried_over(&tx, &carried_over);
     build_db_indices(&tx);
+    init_data_version(&tx);
 
     tx.commit().unwrap();
 }
@@ -1859,6 +2768,7 @@ pub fn rebuild_tracks_in_db() {
         let mut ctx = create_db_context(&mut tx);
         rebuild_tracks(&mut ctx);
     }
+    bump_data_version(&tx);
     tx.commit().unwrap();
 }
 
@@ -1871,6 +2781,7 @@ pub fn rebuild_cars_in_db() {
         let mut ctx = create_db_context(&mut tx);
         rebuild_cars(&mut ctx);
     }
+    bump_data_version(&tx);
     tx.commit().unwrap();
 }
 
@@ -1884,6 +2795,7 @@ pub fn rebuild_car_classes_in_db() {
         let mut ctx = create_db_context(&mut tx);
         rebuild_car_classes(&mut ctx);
     }
+    bump_data_version(&tx);
     tx.commit().unwrap();
 }
 
@@ -1896,24 +2808,615 @@ pub fn rebuild_seasons_in_db() {
         let mut ctx = create_db_context(&mut tx);
         rebuild_seasons(&mut ctx);
     }
+    bump_data_version(&tx);
     tx.commit().unwrap();
 }
 
-pub fn rebuild_site_teams_in_db() {
+// Standings are indexed by race week, week n holding the standings after week n was raced.
+// Driver standings are only kept for site team members.
+pub fn replace_season_standings_in_db(
+    season_id: i64,
+    car_class_id: i64,
+    weekly_driver_standings: &Vec<Vec<Value>>,
+    weekly_team_standings: &Vec<Vec<Value>>,
+    member_cust_ids: &HashSet<i64>)
+{
+    let mut con = create_db_connection();
+    let mut tx = con.transaction().unwrap();
+    {
+        tx.execute("DELETE FROM season_driver_standing WHERE season_id = ? AND car_class_id = ?", (season_id, car_class_id)).unwrap();
+        tx.execute("DELETE FROM season_team_standing WHERE season_id = ? AND car_class_id = ?", (season_id, car_class_id)).unwrap();
+
+        let mut ctx = create_db_context(&mut tx);
+        for (race_week_num, standings) in weekly_driver_standings.iter().enumerate() {
+            for standing in standings {
+                if member_cust_ids.contains(&standing["cust_id"].as_i64().unwrap()) {
+                    add_season_driver_standing_to_db(&mut ctx, season_id, car_class_id, race_week_num as i64, standing);
+                }
+            }
+        }
+        for (race_week_num, standings) in weekly_team_standings.iter().enumerate() {
+            for standing in standings {
+                add_season_team_standing_to_db(&mut ctx, season_id, car_class_id, race_week_num as i64, standing);
+            }
+        }
+    }
+    bump_data_version(&tx);
+    tx.commit().unwrap();
+}
+
+pub fn rebuild_season_schedules_in_db() {
+    let mut con = create_db_connection();
+    let mut tx = con.transaction().unwrap();
+    {
+        tx.execute("DELETE FROM season_schedule", ()).unwrap(); // deletes all rows
+
+        let mut ctx = create_db_context(&mut tx);
+        rebuild_season_schedules(&mut ctx);
+    }
+    bump_data_version(&tx);
+    tx.commit().unwrap();
+}
+
+pub fn replace_member_stats_in_db(cust_id: i64) {
+    let contents = fs::read_to_string(get_member_stats_cache_path(cust_id)).unwrap();
+    let stats: Value = serde_json::from_str(&contents).unwrap();
+
     let mut con = create_db_connection();
     let mut tx = con.transaction().unwrap();
     {
-        tx.execute("DELETE FROM site_team", ()).unwrap(); // deletes all rows
-        tx.execute("DELETE FROM site_team_member", ()).unwrap(); // deletes all rows
-        tx.execute("DELETE FROM site_team_team", ()).unwrap(); // deletes all rows
+        tx.execute("DELETE FROM member_profile WHERE cust_id = ?", (cust_id,)).unwrap();
+        tx.execute("DELETE FROM member_career_stat WHERE cust_id = ?", (cust_id,)).unwrap();
+        tx.execute("DELETE FROM member_yearly_stat WHERE cust_id = ?", (cust_id,)).unwrap();
 
         let mut ctx = create_db_context(&mut tx);
-        rebuild_site_teams(&mut ctx);
+        add_member_stats_to_db(&mut ctx, &stats);
     }
+    bump_data_version(&tx);
     tx.commit().unwrap();
 }
 
+// Replaces the site teams in the db, including changes made through the api, with the data file
+pub fn rebuild_site_teams_in_db() {
+    let site_teams = read_site_teams_data_file();
+
+    let mut con = create_db_connection();
+    let tx = con.transaction().unwrap();
+    tx.execute("DELETE FROM site_team", ()).unwrap(); // deletes all rows
+    tx.execute("DELETE FROM site_team_alias", ()).unwrap(); // deletes all rows
+    tx.execute("DELETE FROM site_team_member", ()).unwrap(); // deletes all rows
+    tx.execute("DELETE FROM site_team_team", ()).unwrap(); // deletes all rows
+
+    rebuild_site_teams(&tx, &site_teams);
+    bump_data_version(&tx);
+    tx.commit().unwrap();
+}
+
+fn query_site_team_id(con: &Connection, name: &str) -> Option<i64> {
+    return con.query_row("SELECT site_team_id FROM site_team WHERE site_team_name = ?1", (name,), |row| row.get(0)).ok();
+}
+
+// true if `name` is the name or an alias of a site team other than `except_site_team_id`
+fn is_site_team_name_taken(con: &Connection, name: &str, except_site_team_id: Option<i64>) -> bool {
+    let count: i64 = con.query_row(
+        r#"SELECT COUNT(*) FROM (
+                SELECT site_team_id FROM site_team WHERE site_team_name = ?1
+                UNION ALL
+                SELECT site_team_id FROM site_team_alias WHERE alias = ?1
+            ) WHERE site_team_id IS NOT ?2"#,
+        (name, except_site_team_id),
+        |row| row.get(0)).unwrap();
+    return count > 0;
+}
+
+fn check_site_team_names(con: &Connection, site_team: &SiteTeamDefinition, except_site_team_id: Option<i64>) -> Result<(), SiteTeamError> {
+    if is_site_team_name_taken(con, &site_team.name, except_site_team_id) {
+        return Err(SiteTeamError::NameTaken(site_team.name.clone()));
+    }
+    for alias in &site_team.aliases {
+        if *alias == site_team.name || is_site_team_name_taken(con, alias, except_site_team_id) {
+            return Err(SiteTeamError::NameTaken(alias.clone()));
+        }
+    }
+    return Ok(());
+}
+
+fn insert_site_team_children(tx: &rusqlite::Transaction, site_team_id: i64, site_team: &SiteTeamDefinition) {
+    for alias in &site_team.aliases {
+        tx.execute("INSERT OR IGNORE INTO site_team_alias VALUES(?1, ?2)", (alias, site_team_id)).unwrap();
+    }
+    for member in &site_team.members {
+        tx.execute("INSERT OR IGNORE INTO site_team_member VALUES(?1, ?2)", (site_team_id, member.cust_id)).unwrap();
+    }
+    for team in &site_team.teams {
+        tx.execute("INSERT OR IGNORE INTO site_team_team VALUES(?1, ?2)", (site_team_id, team.team_id)).unwrap();
+    }
+}
+
+fn delete_site_team_children(tx: &rusqlite::Transaction, site_team_id: i64) {
+    tx.execute("DELETE FROM site_team_alias WHERE site_team_id = ?", (site_team_id,)).unwrap();
+    tx.execute("DELETE FROM site_team_member WHERE site_team_id = ?", (site_team_id,)).unwrap();
+    tx.execute("DELETE FROM site_team_team WHERE site_team_id = ?", (site_team_id,)).unwrap();
+}
+
+fn insert_site_team(tx: &rusqlite::Transaction, site_team: &SiteTeamDefinition) -> Result<i64, SiteTeamError> {
+    check_site_team_names(tx, site_team, None)?;
+
+    tx.execute(
+        "INSERT INTO site_team(site_team_name, discord_hook_url, team_report_discord_hook_url) VALUES(?1, ?2, ?3)",
+        (&site_team.name, &site_team.discord_hook_url, &site_team.team_report_discord_hook_url)).unwrap();
+    let site_team_id = tx.last_insert_rowid();
+
+    insert_site_team_children(tx, site_team_id, site_team);
+    return Ok(site_team_id);
+}
+
+pub fn create_site_team_in_db(con: &mut Connection, site_team: &SiteTeamDefinition) -> Result<(), SiteTeamError> {
+    let tx = con.transaction().unwrap();
+    insert_site_team(&tx, site_team)?;
+    bump_data_version(&tx);
+    tx.commit().unwrap();
+    return Ok(());
+}
+
+// Applies `change` to the site team called `name` (aliases are not considered) in a single transaction
+fn modify_site_team_in_db<F>(con: &mut Connection, name: &str, change: F) -> Result<(), SiteTeamError>
+    where F: FnOnce(&rusqlite::Transaction, i64) -> Result<(), SiteTeamError>
+{
+    let tx = con.transaction().unwrap();
+    let site_team_id = query_site_team_id(&tx, name).ok_or(SiteTeamError::NotFound)?;
+    change(&tx, site_team_id)?;
+    bump_data_version(&tx);
+    tx.commit().unwrap();
+    return Ok(());
+}
+
+// Everything about the site team is replaced, including its name
+pub fn replace_site_team_in_db(con: &mut Connection, name: &str, site_team: &SiteTeamDefinition) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        check_site_team_names(tx, site_team, Some(site_team_id))?;
+        tx.execute(
+            "UPDATE site_team SET site_team_name = ?2, discord_hook_url = ?3, team_report_discord_hook_url = ?4 WHERE site_team_id = ?1",
+            (site_team_id, &site_team.name, &site_team.discord_hook_url, &site_team.team_report_discord_hook_url)).unwrap();
+        // team managers keep managing the renamed team
+        tx.execute("UPDATE api_token SET site_team_name = ?2 WHERE site_team_name = ?1", (name, &site_team.name)).unwrap();
+        delete_site_team_children(tx, site_team_id);
+        insert_site_team_children(tx, site_team_id, site_team);
+        return Ok(());
+    });
+}
+
+pub fn delete_site_team_from_db(con: &mut Connection, name: &str) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        delete_site_team_children(tx, site_team_id);
+        tx.execute("DELETE FROM site_team WHERE site_team_id = ?", (site_team_id,)).unwrap();
+        // so a new team by the same name does not inherit its managers
+        tx.execute("DELETE FROM api_token WHERE site_team_name = ?", (name,)).unwrap();
+        return Ok(());
+    });
+}
+
+pub fn add_site_team_alias_to_db(con: &mut Connection, name: &str, alias: &str) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        if alias == name || is_site_team_name_taken(tx, alias, Some(site_team_id)) {
+            return Err(SiteTeamError::NameTaken(alias.to_owned()));
+        }
+        tx.execute("INSERT OR IGNORE INTO site_team_alias VALUES(?1, ?2)", (alias, site_team_id)).unwrap();
+        return Ok(());
+    });
+}
+
+pub fn remove_site_team_alias_from_db(con: &mut Connection, name: &str, alias: &str) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        let removed = tx.execute("DELETE FROM site_team_alias WHERE site_team_id = ?1 AND alias = ?2", (site_team_id, alias)).unwrap();
+        return if removed > 0 { Ok(()) } else { Err(SiteTeamError::NotFound) };
+    });
+}
+
+pub fn add_site_team_member_to_db(con: &mut Connection, name: &str, cust_id: i64) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        tx.execute("INSERT OR IGNORE INTO site_team_member VALUES(?1, ?2)", (site_team_id, cust_id)).unwrap();
+        return Ok(());
+    });
+}
+
+pub fn remove_site_team_member_from_db(con: &mut Connection, name: &str, cust_id: i64) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        let removed = tx.execute("DELETE FROM site_team_member WHERE site_team_id = ?1 AND cust_id = ?2", (site_team_id, cust_id)).unwrap();
+        return if removed > 0 { Ok(()) } else { Err(SiteTeamError::NotFound) };
+    });
+}
+
+pub fn add_site_team_team_to_db(con: &mut Connection, name: &str, team_id: i64) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        tx.execute("INSERT OR IGNORE INTO site_team_team VALUES(?1, ?2)", (site_team_id, team_id)).unwrap();
+        return Ok(());
+    });
+}
+
+pub fn remove_site_team_team_from_db(con: &mut Connection, name: &str, team_id: i64) -> Result<(), SiteTeamError> {
+    return modify_site_team_in_db(con, name, |tx, site_team_id| {
+        let removed = tx.execute("DELETE FROM site_team_team WHERE site_team_id = ?1 AND team_id = ?2", (site_team_id, team_id)).unwrap();
+        return if removed > 0 { Ok(()) } else { Err(SiteTeamError::NotFound) };
+    });
+}
+
+// Site teams of the db about to be rebuilt, from the data file if there is no usable db yet
+fn read_site_teams_for_rebuild() -> Vec<SiteTeamDefinition> {
+    if get_sqlite_db_file().exists() {
+        match try_query_site_team_definitions(&create_db_connection()) {
+            Ok(site_teams) if !site_teams.is_empty() => return site_teams,
+            // a db rebuilt before the site teams file was added, or one that never had any
+            Ok(_) => println!("The existing db has no site teams, using {}", get_site_teams_data_file().display()),
+            Err(_) => println!("Site teams of the existing db are unreadable, using {}", get_site_teams_data_file().display()),
+        }
+    }
+    return read_site_teams_data_file();
+}
+
+pub struct ApiToken {
+    pub token_hash: String,
+    pub name: String,
+    pub role: String,
+    pub site_team_name: Option<String>,
+    pub created_at: String,
+}
+
+fn insert_api_token(con: &Connection, token: &ApiToken) -> rusqlite::Result<usize> {
+    return con.execute(
+        "INSERT INTO api_token VALUES(?1, ?2, ?3, ?4, ?5)",
+        (&token.token_hash, &token.name, &token.role, &token.site_team_name, &token.created_at));
+}
+
+// Err if a token with the same name exists
+pub fn add_api_token_to_db(token: &ApiToken) -> rusqlite::Result<()> {
+    let con = create_db_connection();
+    insert_api_token(&con, token)?;
+    return Ok(());
+}
+
+// false if there was no token with this name
+pub fn remove_api_token_from_db(name: &str) -> bool {
+    let con = create_db_connection();
+    return con.execute("DELETE FROM api_token WHERE name = ?", (name,)).unwrap() > 0;
+}
+
+pub fn query_api_token(con: &Connection, token_hash: &str) -> Option<ApiToken> {
+    return con.query_row(
+        "SELECT token_hash, name, role, site_team_name, created_at FROM api_token WHERE token_hash = ?",
+        (token_hash,),
+        |row| Ok(ApiToken{
+            token_hash: row.get(0)?,
+            name: row.get(1)?,
+            role: row.get(2)?,
+            site_team_name: row.get(3)?,
+            created_at: row.get(4)?,
+        })).ok();
+}
+
+fn try_query_api_tokens(con: &Connection) -> rusqlite::Result<Vec<ApiToken>> {
+    let mut stmt = con.prepare("SELECT token_hash, name, role, site_team_name, created_at FROM api_token")?;
+    let tokens = stmt.query_map((), |row| Ok(ApiToken{
+        token_hash: row.get(0)?,
+        name: row.get(1)?,
+        role: row.get(2)?,
+        site_team_name: row.get(3)?,
+        created_at: row.get(4)?,
+    }))?.collect();
+    return tokens;
+}
+
+// Tokens of the db about to be rebuilt, none if there is no usable db yet
+fn read_api_tokens_for_rebuild() -> Vec<ApiToken> {
+    if !get_sqlite_db_file().exists() {
+        return Vec::new();
+    }
+    return try_query_api_tokens(&create_db_connection()).unwrap_or_else(|_error| {
+        println!("Api tokens of the existing db are unreadable, they have to be created again");
+        return Vec::new();
+    });
+}
+
+// What a sync job syncs, the same as the corresponding command line options
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SyncJobRequest {
+    /// Every session of the drivers, or the last 10 days only if partial
+    CustIds { cust_ids: Vec<i64>, #[serde(default)] partial: bool },
+    SubsessionIds { subsession_ids: Vec<i64> },
+    Season { year: i32, quarter: i32, week: Option<i32> },
+    /// Cars and car classes
+    CarInfos,
+    TrackInfos,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SyncJob {
+    pub sync_job_id: i64,
+    pub request: SyncJobRequest,
+    /// Name of the api token that requested the job
+    pub requested_by: String,
+    /// queued, running, done or failed
+    pub status: String,
+    /// searching or downloading
+    pub stage: Option<String>,
+    pub progress_done: i64,
+    pub progress_total: i64,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+const SYNC_JOB_COLUMNS: &str =
+    "sync_job_id, request, requested_by, status, stage, progress_done, progress_total, error, created_at, started_at, finished_at";
+
+fn sync_job_from_row(row: &rusqlite::Row) -> rusqlite::Result<SyncJob> {
+    let request: String = row.get(1)?;
+    return Ok(SyncJob{
+        sync_job_id: row.get(0)?,
+        request: serde_json::from_str(&request).unwrap(),
+        requested_by: row.get(2)?,
+        status: row.get(3)?,
+        stage: row.get(4)?,
+        progress_done: row.get(5)?,
+        progress_total: row.get(6)?,
+        error: row.get(7)?,
+        created_at: row.get(8)?,
+        started_at: row.get(9)?,
+        finished_at: row.get(10)?,
+    });
+}
+
+fn sync_job_timestamp() -> String {
+    return chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+}
+
+fn insert_sync_job(con: &Connection, job: &SyncJob) {
+    con.execute(
+        &format!("INSERT INTO sync_job({SYNC_JOB_COLUMNS}) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"),
+        rusqlite::params![
+            job.sync_job_id,
+            serde_json::to_string(&job.request).unwrap(),
+            job.requested_by,
+            job.status,
+            job.stage,
+            job.progress_done,
+            job.progress_total,
+            job.error,
+            job.created_at,
+            job.started_at,
+            job.finished_at,
+        ]).unwrap();
+}
+
+pub fn add_sync_job_to_db(con: &Connection, request: &SyncJobRequest, requested_by: &str) -> SyncJob {
+    con.execute(
+        "INSERT INTO sync_job(request, requested_by, status, progress_done, progress_total, created_at) VALUES(?1, ?2, 'queued', 0, 0, ?3)",
+        (serde_json::to_string(request).unwrap(), requested_by, sync_job_timestamp())).unwrap();
+    return query_sync_job(con, con.last_insert_rowid()).unwrap();
+}
+
+pub fn query_sync_job(con: &Connection, sync_job_id: i64) -> Option<SyncJob> {
+    return con.query_row(
+        &format!("SELECT {SYNC_JOB_COLUMNS} FROM sync_job WHERE sync_job_id = ?"),
+        (sync_job_id,),
+        sync_job_from_row).ok();
+}
+
+fn try_query_sync_jobs(con: &Connection, limit: i64) -> rusqlite::Result<Vec<SyncJob>> {
+    let mut stmt = con.prepare(&format!("SELECT {SYNC_JOB_COLUMNS} FROM sync_job ORDER BY sync_job_id DESC LIMIT ?"))?;
+    let jobs = stmt.query_map((limit,), sync_job_from_row)?.collect();
+    return jobs;
+}
+
+// Newest first
+pub fn query_recent_sync_jobs(con: &Connection, limit: i64) -> Vec<SyncJob> {
+    return try_query_sync_jobs(con, limit).unwrap();
+}
+
+// Marks the oldest queued job as running and returns it, Ok(None) if none is queued. Errors are
+// returned so a busy db can't take the worker down.
+pub fn start_next_sync_job(con: &mut Connection) -> rusqlite::Result<Option<SyncJob>> {
+    let tx = con.transaction()?;
+    let sync_job_id: Option<i64> = tx.query_row(
+        "SELECT sync_job_id FROM sync_job WHERE status = 'queued' ORDER BY sync_job_id LIMIT 1",
+        (),
+        |row| row.get(0)).optional()?;
+    let Some(sync_job_id) = sync_job_id else {
+        return Ok(None);
+    };
+    tx.execute(
+        "UPDATE sync_job SET status = 'running', started_at = ?2 WHERE sync_job_id = ?1",
+        (sync_job_id, sync_job_timestamp()))?;
+    let job = query_sync_job(&tx, sync_job_id);
+    tx.commit()?;
+    return Ok(job);
+}
+
+pub fn set_sync_job_progress(con: &Connection, sync_job_id: i64, stage: &str, done: usize, total: usize) -> rusqlite::Result<()> {
+    con.execute(
+        "UPDATE sync_job SET stage = ?2, progress_done = ?3, progress_total = ?4 WHERE sync_job_id = ?1",
+        (sync_job_id, stage, done as i64, total as i64))?;
+    return Ok(());
+}
+
+// Failed if there is an error
+pub fn finish_sync_job(con: &Connection, sync_job_id: i64, error: &Option<String>) -> rusqlite::Result<()> {
+    let status = if error.is_some() { "failed" } else { "done" };
+    con.execute(
+        "UPDATE sync_job SET status = ?2, error = ?3, finished_at = ?4 WHERE sync_job_id = ?1",
+        (sync_job_id, status, error, sync_job_timestamp()))?;
+    return Ok(());
+}
+
+// Jobs still running when the server stopped, they are not resumed
+pub fn fail_interrupted_sync_jobs(con: &Connection) {
+    con.execute(
+        "UPDATE sync_job SET status = 'failed', error = 'Interrupted by a server restart', finished_at = ?1 WHERE status = 'running'",
+        (sync_job_timestamp(),)).unwrap();
+}
+
+fn read_sync_jobs_for_rebuild() -> Vec<SyncJob> {
+    if !get_sqlite_db_file().exists() {
+        return Vec::new();
+    }
+    return try_query_sync_jobs(&create_db_connection(), -1).unwrap_or_else(|_error| {
+        println!("Sync jobs of the existing db are unreadable, they are dropped");
+        return Vec::new();
+    });
+}
+
+// Start time of the driver's first session in the db, as stored (2009-11-08 16:42:29+00:00)
+pub fn query_earliest_session_start_time(con: &Connection, cust_id: i64) -> Option<String> {
+    let (sql, params) = Query::select()
+        .expr(Func::min(Expr::col((Subsession::Table, Subsession::StartTime))))
+        .from(DriverResult::Table)
+        .join_driver_result_to_subsession()
+        .and_where(Expr::col((DriverResult::Table, DriverResult::CustId)).eq(cust_id))
+        .build_rusqlite(SqliteQueryBuilder);
+
+    return con.query_row(sql.as_str(), &*params.as_params(), |row| row.get(0)).unwrap();
+}
+
+pub fn query_backfill_windows(con: &Connection, cust_id: i64) -> HashSet<String> {
+    let mut stmt = con.prepare("SELECT start_date FROM backfill_window WHERE cust_id = ?").unwrap();
+    return stmt.query_map((cust_id,), |row| row.get(0)).unwrap().map(|start_date| start_date.unwrap()).collect();
+}
+
+pub fn query_backfill_subsession_ids(con: &Connection, cust_id: i64) -> Vec<i64> {
+    let mut stmt = con.prepare("SELECT subsession_id FROM backfill_subsession WHERE cust_id = ?").unwrap();
+    return stmt.query_map((cust_id,), |row| row.get(0)).unwrap().map(|subsession_id| subsession_id.unwrap()).collect();
+}
+
+// The window and what was found in it are stored together, a window is never half checkpointed
+pub fn add_backfill_window(con: &mut Connection, cust_id: i64, start_date: &str, subsession_ids: &Vec<i64>) {
+    let tx = con.transaction().unwrap();
+    for subsession_id in subsession_ids {
+        tx.execute("INSERT OR IGNORE INTO backfill_subsession VALUES(?1, ?2)", (cust_id, subsession_id)).unwrap();
+    }
+    tx.execute("INSERT OR IGNORE INTO backfill_window VALUES(?1, ?2)", (cust_id, start_date)).unwrap();
+    tx.commit().unwrap();
+}
+
+pub fn clear_backfill_checkpoints(con: &Connection, cust_id: i64) {
+    con.execute("DELETE FROM backfill_window WHERE cust_id = ?", (cust_id,)).unwrap();
+    con.execute("DELETE FROM backfill_subsession WHERE cust_id = ?", (cust_id,)).unwrap();
+}
+
+pub fn query_subsession_ids_not_in_db(con: &Connection, subsession_ids: &Vec<i64>) -> Vec<i64> {
+    let mut stmt = con.prepare("SELECT 1 FROM subsession WHERE subsession_id = ?").unwrap();
+    return subsession_ids.iter().filter(|subsession_id| !stmt.exists((subsession_id,)).unwrap()).copied().collect();
+}
+
+pub struct DriverSyncState {
+    pub cust_id: i64,
+    pub member_since: Option<String>,
+    pub history_complete: bool,
+    pub searched_until: Option<String>,
+}
+
+fn insert_driver_sync_state(con: &Connection, state: &DriverSyncState) {
+    con.execute(
+        "INSERT INTO driver_sync_state VALUES(?1, ?2, ?3, ?4)",
+        (state.cust_id, &state.member_since, state.history_complete, &state.searched_until)).unwrap();
+}
+
+pub fn query_driver_history_complete(con: &Connection, cust_id: i64) -> bool {
+    return con.query_row(
+        "SELECT history_complete FROM driver_sync_state WHERE cust_id = ?",
+        (cust_id,),
+        |row| row.get(0)).unwrap_or(false);
+}
+
+// member_since is kept if already known and None is given
+pub fn set_driver_history_complete(con: &Connection, cust_id: i64, member_since: Option<String>) {
+    con.execute(
+        r#"INSERT INTO driver_sync_state(cust_id, member_since, history_complete) VALUES(?1, ?2, 1)
+            ON CONFLICT(cust_id) DO UPDATE SET
+                member_since = COALESCE(excluded.member_since, member_since),
+                history_complete = 1"#,
+        (cust_id, member_since)).unwrap();
+}
+
+pub fn query_driver_searched_until(con: &Connection, cust_id: i64) -> Option<String> {
+    return con.query_row(
+        "SELECT searched_until FROM driver_sync_state WHERE cust_id = ?",
+        (cust_id,),
+        |row| row.get(0)).unwrap_or(None);
+}
+
+// Never moves the watermark back
+pub fn set_driver_searched_until(con: &Connection, cust_id: i64, searched_until: &str) {
+    con.execute(
+        r#"INSERT INTO driver_sync_state(cust_id, history_complete, searched_until) VALUES(?1, 0, ?2)
+            ON CONFLICT(cust_id) DO UPDATE SET
+                searched_until = MAX(COALESCE(searched_until, ''), excluded.searched_until)"#,
+        (cust_id, searched_until)).unwrap();
+}
+
+fn try_query_driver_sync_states(con: &Connection) -> rusqlite::Result<Vec<DriverSyncState>> {
+    let mut stmt = con.prepare("SELECT cust_id, member_since, history_complete, searched_until FROM driver_sync_state")?;
+    let states = stmt.query_map((), |row| Ok(DriverSyncState{
+        cust_id: row.get(0)?,
+        member_since: row.get(1)?,
+        history_complete: row.get(2)?,
+        searched_until: row.get(3)?,
+    }))?.collect();
+    return states;
+}
+
+// Without them every member is checked against iRacing again by the next partial sync
+fn read_driver_sync_states_for_rebuild() -> Vec<DriverSyncState> {
+    if !get_sqlite_db_file().exists() {
+        return Vec::new();
+    }
+    return try_query_driver_sync_states(&create_db_connection()).unwrap_or_else(|_error| {
+        println!("Driver sync states of the existing db are unreadable, they are dropped");
+        return Vec::new();
+    });
+}
+
+pub fn write_site_teams_data_file(path: &Path) {
+    let con = create_db_connection();
+    let file = SiteTeamsFile{ site_teams: query_site_team_definitions(&con) };
+    fs::write(path, serde_json::to_string_pretty(&file).unwrap()).unwrap();
+}
+
+// Site teams, api tokens, sync jobs and driver sync states aren't derived from the cache, so they
+// are read before a rebuild deletes the db and carried over.
+struct CarriedOver {
+    site_teams: Vec<SiteTeamDefinition>,
+    api_tokens: Vec<ApiToken>,
+    sync_jobs: Vec<SyncJob>,
+    driver_sync_states: Vec<DriverSyncState>,
+}
+
+fn read_carried_over_for_rebuild() -> CarriedOver {
+    return CarriedOver{
+        site_teams: read_site_teams_for_rebuild(),
+        api_tokens: read_api_tokens_for_rebuild(),
+        sync_jobs: read_sync_jobs_for_rebuild(),
+        driver_sync_states: read_driver_sync_states_for_rebuild(),
+    };
+}
+
+fn insert_carried_over(tx: &rusqlite::Transaction, carried_over: &CarriedOver) {
+    rebuild_site_teams(tx, &carried_over.site_teams);
+    for api_token in &carried_over.api_tokens {
+        insert_api_token(tx, api_token).unwrap();
+    }
+    for sync_job in &carried_over.sync_jobs {
+        insert_sync_job(tx, sync_job);
+    }
+    for driver_sync_state in &carried_over.driver_sync_states {
+        insert_driver_sync_state(tx, driver_sync_state);
+    }
+}
+
 pub fn rebuild_db() {
+    let carried_over = read_carried_over_for_rebuild();
+
     fs::remove_file(get_sqlite_db_file()).ok(); // ignore error
 
     let mut con = create_db_connection();
@@ -1929,11 +3432,14 @@ pub fn rebuild_db() {
         rebuild_cars(&mut ctx);
         rebuild_car_classes(&mut ctx);
         rebuild_seasons(&mut ctx);
-        rebuild_site_teams(&mut ctx);
+        rebuild_season_schedules(&mut ctx);
         rebuild_sessions(&mut ctx);
+        rebuild_member_stats(&mut ctx);
     }
+    insert_carried_over(&tx, &carried_over);
     build_db_indices(&tx);
-    
+    init_data_version(&tx);
+
     tx.commit().unwrap();
 }
 
@@ -1962,8 +3468,114 @@ pub fn update_db() {
 
         {
             let mut ctx = crate::db::create_db_context(&mut tx);
-            add_sessions_to_db(&mut ctx, sessions_not_in_db.into_iter().map(|id| get_session_cache_path(id)));
+            add_sessions_to_db(&mut ctx, sessions_not_in_db);
         }
     }
+    bump_data_version(&tx);
     tx.commit().unwrap();
-}
\ No newline at end of file
+}
+
+pub struct MemberProfileData {
+    pub display_name: String,
+    pub member_since: Option<String>,
+    pub club_name: Option<String>,
+    pub synced_at: String,
+}
+
+pub fn query_member_profile(con: &Connection, cust_id: i64) -> Option<MemberProfileData> {
+    return con.query_row(
+        "SELECT display_name, member_since, club_name, synced_at FROM member_profile WHERE cust_id = ?",
+        (cust_id,),
+        |row| Ok(MemberProfileData{
+            display_name: row.get(0)?,
+            member_since: row.get(1)?,
+            club_name: row.get(2)?,
+            synced_at: row.get(3)?,
+        })).ok();
+}
+
+// Positions are in class and 1 based
+pub struct MemberStatsData {
+    pub year: Option<i32>, // None for career totals
+    pub category_id: i32,
+    pub starts: i64,
+    pub wins: i64,
+    pub top5: i64,
+    pub poles: i64,
+    pub laps: i64,
+    pub laps_led: Option<i64>, // not known for the computed stats
+    pub avg_incidents: f64,
+    pub avg_start_position: f64,
+    pub avg_finish_position: f64,
+}
+
+fn member_stats_data_from_row(row: &rusqlite::Row, year: Option<i32>) -> rusqlite::Result<MemberStatsData> {
+    return Ok(MemberStatsData{
+        year,
+        category_id: row.get(0)?,
+        starts: row.get(1)?,
+        wins: row.get(2)?,
+        top5: row.get(3)?,
+        poles: row.get(4)?,
+        laps: row.get(5)?,
+        laps_led: row.get(6)?,
+        avg_incidents: row.get(7)?,
+        avg_start_position: row.get(8)?,
+        avg_finish_position: row.get(9)?,
+    });
+}
+
+// iRacing's career totals followed by its yearly stats
+pub fn query_member_official_stats(con: &Connection, cust_id: i64) -> Vec<MemberStatsData> {
+    let columns = "category_id, starts, wins, top5, poles, laps, laps_led, avg_incidents, avg_start_position, avg_finish_position";
+
+    let mut stmt = con.prepare(&format!("SELECT {columns} FROM member_career_stat WHERE cust_id = ? ORDER BY category_id")).unwrap();
+    let mut result: Vec<MemberStatsData> = stmt.query_map((cust_id,), |row| member_stats_data_from_row(row, None))
+        .unwrap().map(|stats| stats.unwrap()).collect();
+
+    let mut stmt = con.prepare(&format!("SELECT {columns}, year FROM member_yearly_stat WHERE cust_id = ? ORDER BY year, category_id")).unwrap();
+    let yearly = stmt.query_map((cust_id,), |row| member_stats_data_from_row(row, Some(row.get(10)?))).unwrap();
+    result.extend(yearly.map(|stats| stats.unwrap()));
+    return result;
+}
+
+// The same numbers computed from the official races in the db, for finding races the db misses.
+// Career totals if per_year is false.
+pub fn query_member_computed_stats(con: &Connection, cust_id: i64, per_year: bool) -> Vec<MemberStatsData> {
+    let mut query = Query::select();
+    query
+        .expr_corrected_license_category()
+        .expr(Func::count(Expr::col((DriverResult::Table, DriverResult::CustId))))
+        .expr(Expr::cust(r#"SUM("driver_result"."finish_position_in_class" = 0)"#))
+        .expr(Expr::cust(r#"SUM("driver_result"."finish_position_in_class" < 5)"#))
+        .expr(Expr::cust(r#"SUM("driver_result"."starting_position_in_class" = 0)"#))
+        .expr(Func::sum(Expr::col((DriverResult::Table, DriverResult::LapsComplete))))
+        .expr(Expr::cust("NULL"))
+        .expr(Func::avg(Expr::col((DriverResult::Table, DriverResult::Incidents))))
+        .expr(Expr::cust(r#"AVG("driver_result"."starting_position_in_class") + 1"#))
+        .expr(Expr::cust(r#"AVG("driver_result"."finish_position_in_class") + 1"#))
+        .expr(Expr::cust(r#"CAST(SUBSTR("subsession"."start_time", 1, 4) AS INTEGER)"#))
+        .from(DriverResult::Table)
+        .join_driver_result_to_subsession()
+        .join_driver_result_to_simsession()
+        .join_subsession_to_track_config()
+        .and_where(Expr::col((DriverResult::Table, DriverResult::CustId)).eq(cust_id))
+        .and_where(is_event_type(EventType::Race))
+        .and_where(is_main_event())
+        .and_where(is_official())
+        .add_group_by([Expr::cust("1")]);
+    if per_year {
+        query.add_group_by([Expr::cust("11")]);
+    }
+    let (sql, params) = query.build_rusqlite(SqliteQueryBuilder);
+
+    let mut stmt = con.prepare(sql.as_str()).unwrap();
+    let mut rows = stmt.query(&*params.as_params()).unwrap();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        let year = if per_year { Some(row.get(10).unwrap()) } else { None };
+        result.push(member_stats_data_from_row(row, year).unwrap());
+    }
+    return result;
+}
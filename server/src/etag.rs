@@ -0,0 +1,88 @@
+// Conditional request support for the read endpoints backed by the db.
+//
+// The ETag is derived from the db data version (see db::bump_data_version),
+// the request URI and the Accept header, so it changes whenever an ingest
+// might have changed the response. Handlers check `matches()` first and skip
+// their queries entirely when the client already has the current response.
+
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use sha2::{Digest, Sha256};
+
+use crate::db::{query_data_version, DbPool};
+
+pub struct DataETag {
+    etag: String,
+    matches: bool,
+}
+
+// If-None-Match uses the weak comparison, so W/ prefixes are ignored
+fn if_none_match_matches(if_none_match: &str, etag: &str) -> bool {
+    return if_none_match.split(',').any(|candidate| {
+        let candidate = candidate.trim();
+        return candidate == "*" || candidate.trim_start_matches("W/") == etag;
+    });
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DataETag {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(db_pool) = request.rocket().state::<DbPool>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        let data_version = query_data_version(&db_pool.get().unwrap());
+
+        let mut hasher = Sha256::new();
+        hasher.update(request.uri().to_string().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(request.headers().get_one("Accept").unwrap_or("").as_bytes());
+        let hash = hasher.finalize();
+        let hash_str: String = hash.iter().take(8).map(|byte| format!("{byte:02x}")).collect();
+
+        let etag = format!("\"{data_version}-{hash_str}\"");
+        let matches = request.headers().get("If-None-Match").any(|value| if_none_match_matches(value, &etag));
+
+        return Outcome::Success(DataETag{ etag, matches });
+    }
+}
+
+impl DataETag {
+    // true if the client's copy is still current
+    pub fn matches(&self) -> bool {
+        return self.matches;
+    }
+
+    pub fn not_modified<R>(self) -> Conditional<R> {
+        return Conditional{ etag: self.etag, body: None };
+    }
+
+    pub fn with<R>(self, body: R) -> Conditional<R> {
+        return Conditional{ etag: self.etag, body: Some(body) };
+    }
+}
+
+// 304 without a body, or the body tagged with the ETag (only if it turned out 200)
+pub struct Conditional<R> {
+    etag: String,
+    body: Option<R>,
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Conditional<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = match self.body {
+            Some(body) => body.respond_to(request)?,
+            None => Response::build().status(Status::NotModified).finalize(),
+        };
+
+        if response.status() == Status::Ok || response.status() == Status::NotModified {
+            response.set_header(Header::new("ETag", self.etag));
+            // cache, but ask every time whether it is still current
+            response.set_header(Header::new("Cache-Control", "no-cache"));
+            response.set_header(Header::new("Vary", "Accept"));
+        }
+        return Ok(response);
+    }
+}
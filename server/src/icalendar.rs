@@ -0,0 +1,71 @@
+// Minimal iCalendar (RFC 5545) writer for the calendar feeds
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+pub enum EventTime {
+    // all-day event
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+pub struct CalendarEvent {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub start: EventTime,
+    pub end: EventTime,
+}
+
+fn escape_text(text: &str) -> String {
+    return text
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n");
+}
+
+fn format_event_time(property: &str, time: &EventTime) -> String {
+    return match time {
+        EventTime::Date(date) => format!("{property};VALUE=DATE:{}", date.format("%Y%m%d")),
+        EventTime::DateTime(date_time) => format!("{property}:{}", date_time.format("%Y%m%dT%H%M%SZ")),
+    };
+}
+
+// Lines longer than 75 octets have to be folded, continuation lines start with a space
+fn push_line(output: &mut String, line: &str) {
+    let mut line_length = 0;
+    for ch in line.chars() {
+        if line_length + ch.len_utf8() > 75 {
+            output.push_str("\r\n ");
+            line_length = 1;
+        }
+        output.push(ch);
+        line_length += ch.len_utf8();
+    }
+    output.push_str("\r\n");
+}
+
+pub fn render_calendar(name: &str, events: &Vec<CalendarEvent>) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+
+    let mut output = String::new();
+    push_line(&mut output, "BEGIN:VCALENDAR");
+    push_line(&mut output, "VERSION:2.0");
+    push_line(&mut output, "PRODID:-//r0mai//iracing-stats//EN");
+    push_line(&mut output, "CALSCALE:GREGORIAN");
+    push_line(&mut output, &format!("X-WR-CALNAME:{}", escape_text(name)));
+
+    for event in events {
+        push_line(&mut output, "BEGIN:VEVENT");
+        push_line(&mut output, &format!("UID:{}", event.uid));
+        push_line(&mut output, &format!("DTSTAMP:{stamp}"));
+        push_line(&mut output, &format_event_time("DTSTART", &event.start));
+        push_line(&mut output, &format_event_time("DTEND", &event.end));
+        push_line(&mut output, &format!("SUMMARY:{}", escape_text(&event.summary)));
+        push_line(&mut output, &format!("DESCRIPTION:{}", escape_text(&event.description)));
+        push_line(&mut output, "END:VEVENT");
+    }
+
+    push_line(&mut output, "END:VCALENDAR");
+    return output;
+}
@@ -0,0 +1,360 @@
+// The iRacing Data API endpoints the client uses, what they take and what they return.
+//
+// Every endpoint answers in one of a few shapes (see ResponseShape). The endpoints
+// here know theirs, so IRacingClient::fetch can read any of them the same way and
+// hand back the deserialized response. Fields the code doesn't use are left out of
+// the response structs, the raw JSON endpoints whose responses get cached as is
+// deserialize to serde_json::Value.
+
+use std::collections::HashMap;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseShape {
+    // the response is the data
+    Direct,
+    // {"link": url}, the linked file is the data
+    Link,
+    // {"data": {"chunk_info": ...}}, the data is the chunk files appended together
+    Chunked,
+    // {"link": url}, the linked file has a "chunk_info" (one extra redirect)
+    LinkedChunks,
+}
+
+impl std::fmt::Display for ResponseShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ResponseShape::Direct => write!(f, "direct"),
+            ResponseShape::Link => write!(f, "link"),
+            ResponseShape::Chunked => write!(f, "chunked"),
+            ResponseShape::LinkedChunks => write!(f, "linked chunks"),
+        };
+    }
+}
+
+pub trait Endpoint {
+    const PATH: &'static str;
+    const SHAPE: ResponseShape;
+    type Response: DeserializeOwned;
+
+    fn params(&self) -> HashMap<&'static str, String>;
+}
+
+// Shapes of the endpoints above and a few more that are handy with --query-iracing-api.
+// Anything else gets its shape detected from the response.
+const SHAPES: &[(&str, ResponseShape)] = &[
+    (MemberGet::PATH, MemberGet::SHAPE),
+    (MemberProfile::PATH, MemberProfile::SHAPE),
+    (MemberCareer::PATH, MemberCareer::SHAPE),
+    (MemberYearly::PATH, MemberYearly::SHAPE),
+    (LookupDrivers::PATH, LookupDrivers::SHAPE),
+    (ResultsGet::PATH, ResultsGet::SHAPE),
+    (SearchSeries::PATH, SearchSeries::SHAPE),
+    (SearchHosted::PATH, SearchHosted::SHAPE),
+    (SeasonList::PATH, SeasonList::SHAPE),
+    (SeriesSeasons::PATH, SeriesSeasons::SHAPE),
+    (TrackGet::PATH, TrackGet::SHAPE),
+    (CarGet::PATH, CarGet::SHAPE),
+    (CarClassGet::PATH, CarClassGet::SHAPE),
+    (SeasonDriverStandings::PATH, SeasonDriverStandings::SHAPE),
+    (SeasonTeamStandings::PATH, SeasonTeamStandings::SHAPE),
+    ("/data/doc", ResponseShape::Direct),
+    ("/data/member/info", ResponseShape::Link),
+    ("/data/member/recent_races", ResponseShape::Link),
+    ("/data/results/lap_data", ResponseShape::LinkedChunks),
+    ("/data/results/lap_chart_data", ResponseShape::LinkedChunks),
+    ("/data/results/event_log", ResponseShape::LinkedChunks),
+    ("/data/series/get", ResponseShape::Link),
+    ("/data/stats/member_recent_races", ResponseShape::Link),
+    ("/data/stats/season_supersession_standings", ResponseShape::LinkedChunks),
+    ("/data/stats/season_tt_standings", ResponseShape::LinkedChunks),
+    ("/data/stats/season_qualify_results", ResponseShape::LinkedChunks),
+];
+
+pub fn known_shape(path: &str) -> Option<ResponseShape> {
+    return SHAPES.iter().find(|(known_path, _)| *known_path == path).map(|(_, shape)| *shape);
+}
+
+fn insert_if_some<T: ToString>(params: &mut HashMap<&'static str, String>, name: &'static str, value: &Option<T>) {
+    if let Some(value) = value {
+        params.insert(name, value.to_string());
+    }
+}
+
+pub struct MemberGet {
+    pub cust_ids: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct MemberGetResponse {
+    pub members: Vec<Member>,
+}
+
+#[derive(Deserialize)]
+pub struct Member {
+    // YYYY-MM-DD
+    pub member_since: String,
+}
+
+impl Endpoint for MemberGet {
+    const PATH: &'static str = "/data/member/get";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = MemberGetResponse;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        let cust_ids: Vec<String> = self.cust_ids.iter().map(|cust_id| cust_id.to_string()).collect();
+        return HashMap::from([("cust_ids", cust_ids.join(","))]);
+    }
+}
+
+pub struct MemberProfile {
+    pub cust_id: i64,
+}
+
+impl Endpoint for MemberProfile {
+    const PATH: &'static str = "/data/member/profile";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([("cust_id", self.cust_id.to_string())]);
+    }
+}
+
+// {"cust_id": .., "stats": [one per category]}, written to the member stats cache as is
+pub struct MemberCareer {
+    pub cust_id: i64,
+}
+
+impl Endpoint for MemberCareer {
+    const PATH: &'static str = "/data/stats/member_career";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([("cust_id", self.cust_id.to_string())]);
+    }
+}
+
+// Like MemberCareer with one stats entry per year and category
+pub struct MemberYearly {
+    pub cust_id: i64,
+}
+
+impl Endpoint for MemberYearly {
+    const PATH: &'static str = "/data/stats/member_yearly";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([("cust_id", self.cust_id.to_string())]);
+    }
+}
+
+pub struct LookupDrivers {
+    // a name or a cust_id
+    pub search_term: String,
+}
+
+#[derive(Deserialize)]
+pub struct DriverLookup {
+    pub cust_id: i64,
+}
+
+impl Endpoint for LookupDrivers {
+    const PATH: &'static str = "/data/lookup/drivers";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = Vec<DriverLookup>;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([("search_term", self.search_term.clone())]);
+    }
+}
+
+pub struct ResultsGet {
+    pub subsession_id: i64,
+}
+
+impl Endpoint for ResultsGet {
+    const PATH: &'static str = "/data/results/get";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    // written to the session cache as is
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([("subsession_id", self.subsession_id.to_string())]);
+    }
+}
+
+// One row of a results search, one per subsession
+#[derive(Deserialize)]
+pub struct SearchResult {
+    pub subsession_id: i64,
+}
+
+// Needs either a season or a start range, the range can be 90 days at most
+#[derive(Default)]
+pub struct SearchSeries {
+    pub season_year: Option<i32>,
+    pub season_quarter: Option<i32>,
+    pub race_week_num: Option<i32>,
+    pub cust_id: Option<i64>,
+    pub start_range_begin: Option<String>,
+    pub start_range_end: Option<String>,
+}
+
+impl Endpoint for SearchSeries {
+    const PATH: &'static str = "/data/results/search_series";
+    const SHAPE: ResponseShape = ResponseShape::Chunked;
+    type Response = Vec<SearchResult>;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        let mut params = HashMap::new();
+        insert_if_some(&mut params, "season_year", &self.season_year);
+        insert_if_some(&mut params, "season_quarter", &self.season_quarter);
+        insert_if_some(&mut params, "race_week_num", &self.race_week_num);
+        insert_if_some(&mut params, "cust_id", &self.cust_id);
+        insert_if_some(&mut params, "start_range_begin", &self.start_range_begin);
+        insert_if_some(&mut params, "start_range_end", &self.start_range_end);
+        return params;
+    }
+}
+
+pub struct SearchHosted {
+    pub cust_id: i64,
+    pub start_range_begin: String,
+    pub start_range_end: String,
+}
+
+impl Endpoint for SearchHosted {
+    const PATH: &'static str = "/data/results/search_hosted";
+    const SHAPE: ResponseShape = ResponseShape::Chunked;
+    type Response = Vec<SearchResult>;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([
+            ("cust_id", self.cust_id.to_string()),
+            ("start_range_begin", self.start_range_begin.clone()),
+            ("start_range_end", self.start_range_end.clone()),
+        ]);
+    }
+}
+
+// a.k.a series list
+pub struct SeasonList {
+    pub season_year: i32,
+    pub season_quarter: i32,
+}
+
+#[derive(Deserialize)]
+pub struct SeasonListResponse {
+    // written to the seasons file as is
+    pub seasons: Vec<serde_json::Value>,
+}
+
+impl Endpoint for SeasonList {
+    const PATH: &'static str = "/data/season/list";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = SeasonListResponse;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([
+            ("season_year", self.season_year.to_string()),
+            ("season_quarter", self.season_quarter.to_string()),
+        ]);
+    }
+}
+
+pub struct SeriesSeasons {
+    pub include_series: bool,
+}
+
+impl Endpoint for SeriesSeasons {
+    const PATH: &'static str = "/data/series/seasons";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::from([("include_series", self.include_series.to_string())]);
+    }
+}
+
+pub struct TrackGet;
+
+impl Endpoint for TrackGet {
+    const PATH: &'static str = "/data/track/get";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::new();
+    }
+}
+
+pub struct CarGet;
+
+impl Endpoint for CarGet {
+    const PATH: &'static str = "/data/car/get";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::new();
+    }
+}
+
+pub struct CarClassGet;
+
+impl Endpoint for CarClassGet {
+    const PATH: &'static str = "/data/carclass/get";
+    const SHAPE: ResponseShape = ResponseShape::Link;
+    type Response = serde_json::Value;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return HashMap::new();
+    }
+}
+
+// Without race_week_num these are the standings after the latest week
+pub struct SeasonStandings {
+    pub season_id: i64,
+    pub car_class_id: i64,
+    pub race_week_num: Option<i64>,
+}
+
+impl SeasonStandings {
+    fn params(&self) -> HashMap<&'static str, String> {
+        let mut params = HashMap::from([
+            ("season_id", self.season_id.to_string()),
+            ("car_class_id", self.car_class_id.to_string()),
+        ]);
+        insert_if_some(&mut params, "race_week_num", &self.race_week_num);
+        return params;
+    }
+}
+
+pub struct SeasonDriverStandings(pub SeasonStandings);
+
+impl Endpoint for SeasonDriverStandings {
+    const PATH: &'static str = "/data/stats/season_driver_standings";
+    const SHAPE: ResponseShape = ResponseShape::LinkedChunks;
+    // stored as is by replace_season_standings_in_db
+    type Response = Vec<serde_json::Value>;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return self.0.params();
+    }
+}
+
+pub struct SeasonTeamStandings(pub SeasonStandings);
+
+impl Endpoint for SeasonTeamStandings {
+    const PATH: &'static str = "/data/stats/season_team_standings";
+    const SHAPE: ResponseShape = ResponseShape::LinkedChunks;
+    type Response = Vec<serde_json::Value>;
+
+    fn params(&self) -> HashMap<&'static str, String> {
+        return self.0.params();
+    }
+}
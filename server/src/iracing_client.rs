@@ -1,47 +1,136 @@
 use std::{
     collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
     sync::atomic::{AtomicI64, Ordering},
     thread::current,
 };
-use chrono::{Utc, DateTime, Days, NaiveDateTime, NaiveDate, FixedOffset, TimeZone, NaiveTime};
+use chrono::{Utc, DateTime, Datelike, Days, NaiveDateTime, NaiveDate, FixedOffset, TimeZone, NaiveTime};
 use serde_json;
-use reqwest::{self, Client, header::HeaderValue};
+use reqwest::{self, Client, Url, cookie::{CookieStore, Jar}, header::HeaderValue};
 use std::time::Instant;
-use lazy_static::lazy_static;
 
-use crate::db::query_all_site_team_members;
+use crate::db::{query_all_site_team_members, query_latest_season, query_subsession_ids_since};
+use crate::subsession_diff::diff_subsessions;
+use crate::iracing_auth::{self, AuthError, OAuthToken, SavedSession};
+use crate::iracing_api::{self, Endpoint, ResponseShape};
 
 const BASEURL: &str = "https://members-ng.iracing.com";
-const CURRENT_YEAR: i32 = 2023;
-const CURRENT_QUARTER: i32 = 3;
+const FIRST_SEASON_YEAR: i32 = 2008;
+const MAX_ATTEMPTS: usize = 10;
+// longest wait for a rate limited login before the request counts as failed
+const MAX_LOGIN_WAIT_SECS: u64 = 300;
+// Searches over date ranges the API chokes on fail consistently, so those give up sooner
+const SEARCH_ATTEMPTS: usize = 3;
+// Bisecting down to a single bad second takes about 23 rejections in a row. Many more
+// than that and the API is down rather than choking on a range.
+const MAX_CONSECUTIVE_REJECTIONS: usize = 32;
+// Covers the current season with some slack for the week between seasons
+pub const STANDINGS_SYNC_DAYS: i64 = 100;
+// Partial syncs of drivers that were never searched only search this far back
+const PARTIAL_SYNC_DAYS: u64 = 10;
+// Partial syncs of the others search from their searched_until watermark minus this.
+// Searches go by start time, so races still running (or not yet processed) at the
+// last search only show up later.
+const SEARCH_OVERLAP_DAYS: u64 = 1;
+// Downloaded subsessions are added to the db this many at a time, an interrupted
+// sync loses at most one batch (and those are still in the cache)
+const SUBSESSION_BATCH_SIZE: usize = 100;
+
+#[derive(Debug)]
+pub enum RequestError {
+    // The API answered with a status that is not worth retrying
+    Rejected(reqwest::StatusCode),
+    RetriesExhausted,
+    // Every attempt got a server error
+    ServerError(reqwest::StatusCode),
+    // A bisected search had to leave out this many rejected seconds
+    RangesSkipped(usize),
+    Auth(AuthError),
+    // The data didn't have the shape the endpoint should answer with
+    UnexpectedResponse(String),
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            RequestError::Rejected(status) => write!(f, "rejected with {status}"),
+            RequestError::RetriesExhausted => write!(f, "failed after several retries"),
+            RequestError::ServerError(status) => write!(f, "failed with {status} after several retries"),
+            RequestError::RangesSkipped(count) => write!(f, "left out {count} rejected seconds"),
+            RequestError::Auth(error) => write!(f, "could not log in, {error}"),
+            RequestError::UnexpectedResponse(error) => write!(f, "got an unexpected response, {error}"),
+        };
+    }
+}
+
+// How far a sync got, reported to the sync job running it (see sync_jobs.rs)
+pub struct SyncProgress {
+    pub stage: &'static str,
+    pub done: usize,
+    pub total: usize,
+}
+
+pub type ProgressSender = tokio::sync::mpsc::UnboundedSender<SyncProgress>;
+
+fn report_progress(progress: Option<&ProgressSender>, stage: &'static str, done: usize, total: usize) {
+    if let Some(progress) = progress {
+        progress.send(SyncProgress{ stage, done, total }).ok(); // ignore error
+    }
+}
 
 pub struct IRacingClient {
     pub client: Client,
+    cookie_jar: Arc<Jar>,
+    // only with OAuth, the legacy login uses the cookies
+    oauth_token: Mutex<Option<OAuthToken>>,
     pub rate_limit_limit: AtomicI64,
     pub rate_limit_remaining: AtomicI64,
     pub rate_limit_reset: AtomicI64,
 }
 
-fn cached_now() -> DateTime<Utc> {
-    lazy_static! {
-        static ref NOW: DateTime<Utc> = Utc::now();
+fn to_api_date_string(date: &DateTime<Utc>) -> String {
+    return date.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+}
+
+fn parse_api_date_string(date: &str) -> DateTime<Utc> {
+    return DateTime::parse_from_rfc3339(date).unwrap().with_timezone(&Utc);
+}
+
+fn next_season(year: i32, quarter: i32) -> (i32, i32) {
+    if quarter == 4 {
+        return (year + 1, 1);
     }
-    return *NOW;
+    return (year, quarter + 1);
 }
 
-fn to_api_date_string(date: &DateTime<Utc>) -> String {
-    return date.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+// The season of today's date. iRacing seasons start around the calendar quarters,
+// off by a week or two, which is fine for knowing how far the season list goes.
+fn current_calendar_season() -> (i32, i32) {
+    let now = Utc::now();
+    return (now.year(), now.month0() as i32 / 3 + 1);
 }
 
-fn extract_subsession_ids_from_response(response: &serde_json::Value) -> Vec<i64> {
-    return response.as_array().unwrap().iter().map(|ses| ses["subsession_id"].as_i64().unwrap()).collect();
+fn extract_subsession_ids(results: &Vec<iracing_api::SearchResult>) -> Vec<i64> {
+    return results.iter().map(|result| result.subsession_id).collect();
 }
 
 impl IRacingClient {
+    // Picks up the session saved by an earlier run, if any
     pub fn new() -> IRacingClient {
-        let client = reqwest::Client::builder().cookie_store(true).build().unwrap();
+        let saved_session = iracing_auth::load_session();
+        let cookie_jar = Arc::new(Jar::default());
+        if let Some(cookies) = &saved_session.cookies {
+            let url = Url::parse(BASEURL).unwrap();
+            for cookie in cookies.split("; ") {
+                cookie_jar.add_cookie_str(cookie, &url);
+            }
+        }
+
+        let client = reqwest::Client::builder().cookie_provider(cookie_jar.clone()).build().unwrap();
         return IRacingClient {
             client,
+            cookie_jar,
+            oauth_token: Mutex::new(saved_session.oauth_token),
             rate_limit_limit: AtomicI64::new(1),
             rate_limit_remaining: AtomicI64::new(1),
             rate_limit_reset: AtomicI64::new(0)
@@ -52,9 +141,23 @@ impl IRacingClient {
         return v.to_str().unwrap().parse::<i64>().unwrap();
     }
 
-    async fn get_with_retry(&self, mut url: String, params: &HashMap<&str, String>) -> Option<serde_json::Value> {
-        for _ in 0..10 {
-            let response_res = self.client.get(&url).query(&params).send().await;
+    async fn get_with_retry(&self, url: String, params: &HashMap<&str, String>) -> Option<serde_json::Value> {
+        return self.try_get_with_retry(url, params, MAX_ATTEMPTS).await
+            .unwrap_or_else(|error| panic!("Failed a request :( {error}"));
+    }
+
+    async fn try_get_with_retry(&self, url: String, params: &HashMap<&str, String>, max_attempts: usize) -> Result<Option<serde_json::Value>, RequestError> {
+        let mut server_error = None;
+        for _ in 0..max_attempts {
+            server_error = None;
+            let mut request = self.client.get(&url).query(&params);
+            // links and chunks are presigned S3 urls, those must not get the token
+            if url.starts_with(&format!("{BASEURL}/")) {
+                if let Some(access_token) = self.current_access_token().await.map_err(RequestError::Auth)? {
+                    request = request.bearer_auth(access_token);
+                }
+            }
+            let response_res = request.send().await;
             if let Err(error) = response_res {
                 println!("Error {error} while requesting {url}");
                 continue;
@@ -71,31 +174,38 @@ impl IRacingClient {
             if let Some(x) = rl_reset { self.rate_limit_reset.store(Self::header_value_to_i64(x), Ordering::Relaxed); }
 
             let text = response.text().await.unwrap();
-            status.is_server_error();
             if status.is_success() {
-                return Some(serde_json::from_str(&text).unwrap());
+                return Ok(Some(serde_json::from_str(&text).unwrap()));
             }
 
             if status.is_server_error() {
                 println!("Request to {url} failed with {status}. Retrying...");
+                server_error = Some(status);
                 continue;
             }
 
             // need to reauth
             if status.as_u16() == 401 {
-                self.auth().await;
+                match self.auth().await {
+                    Ok(()) => {},
+                    Err(AuthError::RateLimited(secs)) if secs <= MAX_LOGIN_WAIT_SECS => {
+                        println!("Login got rate limited, waiting {secs} seconds");
+                        tokio::time::sleep(tokio::time::Duration::from_secs(secs)).await;
+                    },
+                    Err(error) => return Err(RequestError::Auth(error)),
+                }
                 continue;
             }
 
             // unauthorized to view session
             if status.as_u16() == 403 {
                 println!("Request to {url} was unauthorized (403)");
-                return None;
+                return Ok(None);
             }
 
             if status.as_u16() == 404 {
                 println!("Request to {url} was not found (404)");
-                return None;
+                return Ok(None);
             }
 
             // rate limit
@@ -107,23 +217,48 @@ impl IRacingClient {
             println!("Request url {}", url);
             println!("Reponse status {}", status);
             println!("Response body {}", text);
-            panic!("Failed a request :(");
+            return Err(RequestError::Rejected(status));
         }
         println!("Params: {:?}", params);
-        panic!("Failed after several retries :(");
+        println!("Failed after several retries :(");
+        if let Some(status) = server_error {
+            return Err(RequestError::ServerError(status));
+        }
+        return Err(RequestError::RetriesExhausted);
     }
 
-    pub async fn get_and_read(&self, suffix: &str, params: &HashMap<&str, String>) -> Option<serde_json::Value> {
-        let pointer_json = self.get_with_retry(format!("{BASEURL}{suffix}"), params).await?;
-        return self.get_with_retry(String::from(pointer_json["link"].as_str().unwrap()), &HashMap::new()).await;
+    // Reads the data of an endpoint answering with the given shape,
+    // following the link and appending the chunk files as needed
+    async fn try_read(&self, suffix: &str, params: &HashMap<&str, String>, shape: ResponseShape, max_attempts: usize) -> Result<Option<serde_json::Value>, RequestError> {
+        let Some(pointer_json) = self.try_get_with_retry(format!("{BASEURL}{suffix}"), params, max_attempts).await? else {
+            return Ok(None);
+        };
+        return match shape {
+            ResponseShape::Direct => Ok(Some(pointer_json)),
+            ResponseShape::Link => self.try_read_link(&pointer_json, max_attempts).await,
+            ResponseShape::Chunked => self.try_read_chunks(&pointer_json["data"]["chunk_info"], max_attempts).await,
+            ResponseShape::LinkedChunks => {
+                let Some(linked_json) = self.try_read_link(&pointer_json, max_attempts).await? else {
+                    return Ok(None);
+                };
+                self.try_read_chunks(&linked_json["chunk_info"], max_attempts).await
+            },
+        };
     }
 
-    async fn get_and_read_chunked_helper(&self, chunk_info: &serde_json::Value) -> Option<serde_json::Value> {
+    async fn try_read_link(&self, pointer_json: &serde_json::Value, max_attempts: usize) -> Result<Option<serde_json::Value>, RequestError> {
+        let Some(link) = pointer_json["link"].as_str() else {
+            return Err(RequestError::UnexpectedResponse("no link".to_owned()));
+        };
+        return self.try_get_with_retry(link.to_owned(), &HashMap::new(), max_attempts).await;
+    }
+
+    async fn try_read_chunks(&self, chunk_info: &serde_json::Value, max_attempts: usize) -> Result<Option<serde_json::Value>, RequestError> {
         let base_url_res = &chunk_info["base_download_url"].as_str();
 
         let mut result_array = serde_json::Value::Array([].to_vec());
         if base_url_res.is_none() {
-            return Some(result_array);
+            return Ok(Some(result_array));
         }
 
         let base_url = base_url_res.unwrap();
@@ -131,35 +266,58 @@ impl IRacingClient {
 
         for file in suffixes {
             let suffix = file.as_str().unwrap();
-            let mut partial_result = self.get_with_retry(format!("{base_url}{suffix}"), &HashMap::new()).await?;
+            let Some(mut partial_result) = self.try_get_with_retry(format!("{base_url}{suffix}"), &HashMap::new(), max_attempts).await? else {
+                return Ok(None);
+            };
             result_array.as_array_mut().unwrap().append(partial_result.as_array_mut().unwrap());
         }
 
-        return Some(result_array);
+        return Ok(Some(result_array));
     }
 
-    // For those requests that have .data.chunk_info directly
-    pub async fn get_and_read_chunked(&self, suffix: &str, params: &HashMap<&str, String>) -> Option<serde_json::Value> {
-        let pointer_json = self.get_with_retry(format!("{BASEURL}{suffix}"), params).await?;
-        let chunk_info = &pointer_json["data"]["chunk_info"];
-        return self.get_and_read_chunked_helper(chunk_info).await;
+    pub async fn fetch<E: Endpoint>(&self, endpoint: &E) -> Option<E::Response> {
+        return self.try_fetch(endpoint, MAX_ATTEMPTS).await
+            .unwrap_or_else(|error| panic!("Failed a request to {} :( {error}", E::PATH));
     }
 
-    // For those requests that have .chunk_info after reading the first s3 url (one extra redirect)
-    pub async fn get_and_read_chunked2(&self, suffix: &str, params: &HashMap<&str, String>) -> Option<serde_json::Value> {
-        let pointer_json = self.get_and_read(suffix, params).await?;
-        let chunk_info = &pointer_json["chunk_info"];
-        return self.get_and_read_chunked_helper(chunk_info).await;
+    pub async fn try_fetch<E: Endpoint>(&self, endpoint: &E, max_attempts: usize) -> Result<Option<E::Response>, RequestError> {
+        let Some(json) = self.try_read(E::PATH, &endpoint.params(), E::SHAPE, max_attempts).await? else {
+            return Ok(None);
+        };
+        return serde_json::from_value(json)
+            .map(Some)
+            .map_err(|error| RequestError::UnexpectedResponse(error.to_string()));
     }
 
-    async fn get_member_since_date(&self, cust_id: i64) -> DateTime<Utc> {
-        let params = HashMap::from([
-            ("cust_ids", cust_id.to_string())
-        ]);
+    // Reads any endpoint, also ones iracing_api doesn't know. Their shape is worked out
+    // from the response: a "chunk_info" in the data or behind the link means chunks.
+    pub async fn get_and_read_smart(&self, suffix: &str, params: &HashMap<&str, String>) -> Option<(serde_json::Value, ResponseShape)> {
+        if let Some(shape) = iracing_api::known_shape(suffix) {
+            return self.try_read(suffix, params, shape, MAX_ATTEMPTS).await
+                .unwrap_or_else(|error| panic!("Failed a request :( {error}"))
+                .map(|json| (json, shape));
+        }
 
-        let res = self.get_and_read("/data/member/get", &params).await.unwrap();
+        let pointer_json = self.get_with_retry(format!("{BASEURL}{suffix}"), params).await?;
+        let result = if pointer_json["data"].get("chunk_info").is_some() {
+            self.try_read_chunks(&pointer_json["data"]["chunk_info"], MAX_ATTEMPTS).await.map(|json| json.map(|json| (json, ResponseShape::Chunked)))
+        } else if pointer_json["link"].is_string() {
+            match self.try_read_link(&pointer_json, MAX_ATTEMPTS).await {
+                Ok(Some(linked_json)) if linked_json.get("chunk_info").is_some() => {
+                    self.try_read_chunks(&linked_json["chunk_info"], MAX_ATTEMPTS).await.map(|json| json.map(|json| (json, ResponseShape::LinkedChunks)))
+                },
+                linked_result => linked_result.map(|json| json.map(|json| (json, ResponseShape::Link))),
+            }
+        } else {
+            Ok(Some((pointer_json, ResponseShape::Direct)))
+        };
+        return result.unwrap_or_else(|error| panic!("Failed a request :( {error}"));
+    }
+
+    async fn get_member_since_date(&self, cust_id: i64) -> DateTime<Utc> {
+        let res = self.fetch(&iracing_api::MemberGet{ cust_ids: vec![cust_id] }).await.unwrap();
 
-        let date_str = res["members"][0]["member_since"].as_str().unwrap();
+        let date_str = &res.members[0].member_since;
 
         // TODO this can be probably done without involving timezones at all
         let tz_utc = FixedOffset::east_opt(0).unwrap(); // hope this is UTC
@@ -172,144 +330,189 @@ impl IRacingClient {
     }
 
     // a.k.a series list
-    async fn get_season_list(&self, year: i32, quarter: i32) -> serde_json::Value {
-        let params = HashMap::from([
-            ("season_year", year.to_string()),
-            ("season_quarter", quarter.to_string()),
-        ]);
-        return self.get_and_read("/data/season/list", &params).await.unwrap();
+    async fn get_season_list(&self, year: i32, quarter: i32) -> iracing_api::SeasonListResponse {
+        return self.fetch(&iracing_api::SeasonList{ season_year: year, season_quarter: quarter }).await.unwrap();
     }
 
+    // Walks the season lists from the first season until iRacing returns an empty one.
+    // An empty list before the latest season already in the db is treated as a gap.
     async fn get_all_season_list(&self) -> serde_json::Value {
+        // An empty quarter only ends the list once it is past today's season and the latest
+        // season in the db, so an empty db still gets every season up to now
+        let latest_known_season = query_latest_season(&crate::db::create_db_connection());
+        let last_season_to_check = latest_known_season.map_or(current_calendar_season(), |latest| latest.max(current_calendar_season()));
+
         let mut seasons = Vec::new();
-        for year in 2008..=CURRENT_YEAR {
-            let last_quarter = if year == CURRENT_YEAR { CURRENT_QUARTER } else { 4 };
-            for quarter in 1..=last_quarter {
-                println!("Syncing season {year}s{quarter}");
-                let mut current_season_list = self.get_season_list(year, quarter).await;
-                seasons.append(current_season_list["seasons"].as_array_mut().unwrap());
+        let (mut year, mut quarter) = (FIRST_SEASON_YEAR, 1);
+        loop {
+            println!("Syncing season {year}s{quarter}");
+            let mut current_seasons = self.get_season_list(year, quarter).await.seasons;
 
+            if current_seasons.is_empty() && (year, quarter) > last_season_to_check {
+                break;
             }
+
+            seasons.append(&mut current_seasons);
+            (year, quarter) = next_season(year, quarter);
         }
         return serde_json::Value::Array(seasons);
     }
 
-    async fn search_series_by_season(&self, year: i32, quarter: i32, week: Option<i32>) -> serde_json::Value {
-        let mut params = HashMap::from([
-            ("season_year", year.to_string()),
-            ("season_quarter", quarter.to_string()),
-        ]);
-        if let Some(week) = week {
-            params.insert("race_week_num", week.to_string());
-        }
-        return self.get_and_read_chunked("/data/results/search_series", &params).await.unwrap();
+
+    async fn search_series_by_season(&self, year: i32, quarter: i32, week: Option<i32>) -> Vec<iracing_api::SearchResult> {
+        return self.fetch(&iracing_api::SearchSeries{
+            season_year: Some(year),
+            season_quarter: Some(quarter),
+            race_week_num: week,
+            ..Default::default()
+        }).await.unwrap();
+    }
+
+    async fn try_search_results<E>(&self, search: E) -> Result<Vec<i64>, RequestError>
+        where E: Endpoint<Response = Vec<iracing_api::SearchResult>>
+    {
+        return match self.try_fetch(&search, SEARCH_ATTEMPTS).await? {
+            Some(results) => Ok(extract_subsession_ids(&results)),
+            None => Ok(Vec::new()),
+        };
     }
 
-    async fn search_series(&self, cust_id: i64, start_date: &DateTime<Utc>, end_date: &DateTime<Utc>) -> serde_json::Value {
-        let params = HashMap::from([
-            ("cust_id", cust_id.to_string()),
-            ("start_range_begin", to_api_date_string(start_date)),
-            ("start_range_end", to_api_date_string(end_date)),
-        ]);
-        return self.get_and_read_chunked("/data/results/search_series", &params).await.unwrap();
+    // The API rejects some date ranges for no apparent reason, with a 500 every time:
+    // https://forums.iracing.com/discussion/comment/523280/#Comment_523280
+    // https://forums.iracing.com/discussion/comment/531430/#Comment_531430
+    // Rejected ranges are bisected and retried until only the offending second is left out,
+    // which makes the search end with RangesSkipped once the rest of the range is searched.
+    // Any other error ends the search, the ids found until then are in subsession_ids.
+    // make_search gets the api date strings of the range to search.
+    async fn search_results_bisecting<E, F>(&self, make_search: F, start_date: DateTime<Utc>, end_date: DateTime<Utc>, subsession_ids: &mut Vec<i64>) -> Result<(), RequestError>
+        where E: Endpoint<Response = Vec<iracing_api::SearchResult>>, F: Fn(String, String) -> E
+    {
+        let one_second = chrono::Duration::seconds(1);
+
+        let mut consecutive_rejections = 0;
+        let mut skipped = 0;
+        let mut ranges = vec![(start_date, end_date)];
+        while let Some((range_start, range_end)) = ranges.pop() {
+            let search = make_search(to_api_date_string(&range_start), to_api_date_string(&range_end));
+            match self.try_search_results(search).await {
+                Ok(mut new_ids) => {
+                    consecutive_rejections = 0;
+                    subsession_ids.append(&mut new_ids);
+                },
+                Err(RequestError::ServerError(status)) if consecutive_rejections < MAX_CONSECUTIVE_REJECTIONS => {
+                    consecutive_rejections += 1;
+                    if range_end - range_start <= one_second {
+                        println!("Skipping {range_start} -> {range_end}, request failed with {status}");
+                        skipped += 1;
+                        continue;
+                    }
+                    let middle = range_start + (range_end - range_start) / 2;
+                    println!("Range {range_start} -> {range_end} was rejected, splitting at {middle}");
+                    ranges.push((middle + one_second, range_end));
+                    ranges.push((range_start, middle));
+                },
+                Err(error) => return Err(error),
+            }
+        }
+        if skipped > 0 {
+            return Err(RequestError::RangesSkipped(skipped));
+        }
+        return Ok(());
     }
 
-    async fn search_hosted(&self, cust_id: i64, start_date: &DateTime<Utc>, end_date: &DateTime<Utc>) -> serde_json::Value {
-        let params = HashMap::from([
-            ("cust_id", cust_id.to_string()),
-            ("start_range_begin", to_api_date_string(start_date)),
-            ("start_range_end", to_api_date_string(end_date)),
-        ]);
-        return self.get_and_read_chunked("/data/results/search_hosted", &params).await.unwrap();
+    // Searches hosted and official sessions of a driver, the found ids are in subsession_ids even if it fails
+    async fn search_driver_window(&self, cust_id: i64, start_date: DateTime<Utc>, end_date: DateTime<Utc>, subsession_ids: &mut Vec<i64>) -> Result<(), RequestError> {
+        println!("Query hosted {start_date} -> {end_date}");
+        let search_hosted = |start_range_begin, start_range_end| iracing_api::SearchHosted{ cust_id, start_range_begin, start_range_end };
+        let hosted_result = self.search_results_bisecting(search_hosted, start_date, end_date, subsession_ids).await;
+        // skipped seconds don't stop the official search
+        if hosted_result.as_ref().is_err_and(|error| !matches!(error, RequestError::RangesSkipped(_))) {
+            return hosted_result;
+        }
+
+        println!("Query official {start_date} -> {end_date}");
+        let search_series = |start_range_begin, start_range_end| iracing_api::SearchSeries{
+            cust_id: Some(cust_id),
+            start_range_begin: Some(start_range_begin),
+            start_range_end: Some(start_range_end),
+            ..Default::default()
+        };
+        let official_result = self.search_results_bisecting(search_series, start_date, end_date, subsession_ids).await;
+        return official_result.and(hosted_result);
     }
 
-    // return subsession_ids may contain duplicates
-    async fn find_subsessions_for_driver(&self, cust_id: i64, partial: bool) -> Vec<i64> {
+    // return subsession_ids may contain duplicates, and whether every window was searched without errors
+    async fn find_subsessions_for_driver(&self, cust_id: i64, partial: bool) -> (Vec<i64>, bool) {
         let start_date;
         if partial {
-            start_date = cached_now().checked_sub_days(Days::new(10)).unwrap();
+            let searched_until = crate::db::query_driver_searched_until(&crate::db::create_db_connection(), cust_id);
+            start_date = match searched_until {
+                Some(searched_until) => parse_api_date_string(&searched_until).checked_sub_days(Days::new(SEARCH_OVERLAP_DAYS)).unwrap(),
+                None => Utc::now().checked_sub_days(Days::new(PARTIAL_SYNC_DAYS)).unwrap(),
+            };
         } else {
             start_date = self.get_member_since_date(cust_id).await;
         }
 
         let mut subsession_ids = Vec::new();
 
-        // hosted
+        // Full searches start at member since, so their windows are the same every time and
+        // the ones an interrupted search finished are skipped, using what they found back then
+        let mut con = crate::db::create_db_connection();
+        let searched_windows = if partial { HashSet::new() } else { crate::db::query_backfill_windows(&con, cust_id) };
+        if !searched_windows.is_empty() {
+            println!("Resuming the search for {cust_id}, {} windows were searched already", searched_windows.len());
+            subsession_ids.append(&mut crate::db::query_backfill_subsession_ids(&con, cust_id));
+        }
+
+        let mut complete = true;
         let mut current_date = start_date;
-        let last_date = cached_now().checked_add_days(Days::new(1)).unwrap();
-
-        // let bad_date = Utc.with_ymd_and_hms(2023, 12, 31, 0, 0, 0).unwrap();
-        // let bad_date = Utc.with_ymd_and_hms(2024, 1, 31, 23, 59, 59).unwrap();
-        // let bad_dates = vec![
-        //     Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap(),
-        //     Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
-        // ];
-        // let bad_dates = vec![
-        //      Utc.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap(),
-        //      Utc.with_ymd_and_hms(2024, 3, 31, 23, 59, 59).unwrap(),
-        // ];
-        // let bad_dates = vec![
-        //      Utc.with_ymd_and_hms(2024, 4, 30, 23, 59, 59).unwrap(),
-        // ];
-        let bad_dates = vec![
-              Utc.with_ymd_and_hms(2024, 5, 31, 23, 59, 59).unwrap(),
-        ];
+        let now = Utc::now();
+        let last_date = now.checked_add_days(Days::new(1)).unwrap();
 
         while current_date < last_date {
-
             // max range allowed is 90. be safe with 89
-            let mut next_date = current_date.checked_add_days(Days::new(89)).unwrap();
-
-            // https://forums.iracing.com/discussion/comment/523280/#Comment_523280
-            // https://forums.iracing.com/discussion/comment/531430/#Comment_531430
-            let mut bad_date = Option::None;            
-
-            for date in &bad_dates {
-                if current_date <= *date && next_date >= *date {
-                    bad_date = Some(date);
-                    break;
-                }
-            }
-
-            if let Some(date) = bad_date {
-                next_date = date.checked_sub_signed(chrono::Duration::seconds(1)).unwrap();
+            let next_date = current_date.checked_add_days(Days::new(89)).unwrap();
+            let window_start = to_api_date_string(&current_date);
+            if searched_windows.contains(&window_start) {
+                current_date = next_date;
+                continue;
             }
 
-            {
-                println!("Query hosted {current_date} -> {next_date}");
-                let hosted_q = self.search_hosted(cust_id, &current_date, &next_date).await;
-                let mut new_ids = extract_subsession_ids_from_response(&hosted_q);
-                subsession_ids.append(&mut new_ids);
+            let mut window_subsession_ids = Vec::new();
+            let window_result = self.search_driver_window(cust_id, current_date, next_date, &mut window_subsession_ids).await;
+            match window_result {
+                // windows still open can get new sessions, they are searched again
+                Ok(()) => if !partial && next_date < now {
+                    crate::db::add_backfill_window(&mut con, cust_id, &window_start, &window_subsession_ids);
+                },
+                // not checkpointed, the next full search tries this window again
+                Err(RequestError::RangesSkipped(count)) => {
+                    println!("Searching {current_date} -> {next_date} for {cust_id} left out {count} rejected seconds");
+                    complete = false;
+                },
+                Err(error) => {
+                    println!("Stopping the search for {cust_id}, request {error}");
+                    subsession_ids.append(&mut window_subsession_ids);
+                    return (subsession_ids, false);
+                },
             }
 
-            {
-                println!("Query official {current_date} -> {next_date}");
-                let official_q = self.search_series(cust_id, &current_date, &next_date).await;
-                let mut new_ids = extract_subsession_ids_from_response(&official_q);
-                subsession_ids.append(&mut new_ids);
-            }
-            
-            if let Some(date) = bad_date {
-                current_date = date.checked_add_signed(chrono::Duration::seconds(1)).unwrap();
-            } else {
-                current_date = next_date;
-            }
+            subsession_ids.append(&mut window_subsession_ids);
+            current_date = next_date;
         }
 
-        return subsession_ids;
+        return (subsession_ids, complete);
     }
 
     async fn find_subsessions_for_season(&self, year: i32, quarter: i32, week: Option<i32>) -> Vec<i64> {
         let series = self.search_series_by_season(year, quarter, week).await;
-        return series.as_array().unwrap().iter().map(|ses| ses["subsession_id"].as_i64().unwrap()).collect();
+        return extract_subsession_ids(&series);
     }
 
     async fn get_cust_id(&self, driver_name: &String) -> i64 {
-        let res = self.get_and_read("/data/lookup/drivers", &HashMap::from([
-            ("search_term", driver_name.clone())
-        ])).await.unwrap();
-        let arr = res.as_array().unwrap();
-        let len = arr.len();
+        let drivers = self.fetch(&iracing_api::LookupDrivers{ search_term: driver_name.clone() }).await.unwrap();
+        let len = drivers.len();
         if len == 0 {
             panic!("Driver {driver_name} not found");
         }
@@ -318,81 +521,159 @@ impl IRacingClient {
             println!("Multiple {len} matches found for {driver_name}");    
         }
 
-        return arr[0]["cust_id"].as_i64().unwrap();
+        return drivers[0].cust_id;
     }
 
-    async fn lookup_driver(&self, cust_id: i64) -> serde_json::Value {
-        return self.get_and_read("/data/lookup/drivers", &HashMap::from([
-            ("cust_id", cust_id.to_string())
-        ])).await.unwrap();
+    async fn lookup_driver(&self, cust_id: i64) -> Vec<iracing_api::DriverLookup> {
+        return self.fetch(&iracing_api::LookupDrivers{ search_term: cust_id.to_string() }).await.unwrap();
     }
 
-    pub async fn get_member_profile(&self, cust_id: i64) -> serde_json::Value {
-        return self.get_and_read("/data/member/profile", &HashMap::from([
-            ("cust_id", cust_id.to_string())
-        ])).await.unwrap();
+    pub async fn get_member_profile(&self, cust_id: i64) -> Option<serde_json::Value> {
+        return self.fetch(&iracing_api::MemberProfile{ cust_id }).await;
+    }
+
+    pub async fn get_member_career_stats(&self, cust_id: i64) -> Option<serde_json::Value> {
+        return self.fetch(&iracing_api::MemberCareer{ cust_id }).await;
+    }
+
+    pub async fn get_member_yearly_stats(&self, cust_id: i64) -> Option<serde_json::Value> {
+        return self.fetch(&iracing_api::MemberYearly{ cust_id }).await;
     }
 
 
     pub async fn get_subsession(&self, subsession_id: i64) -> Option<serde_json::Value> {
-        return self.get_and_read("/data/results/get", &HashMap::from([
-            ("subsession_id", subsession_id.to_string())
-        ])).await;
+        return self.fetch(&iracing_api::ResultsGet{ subsession_id }).await;
     }
 
-    pub async fn get_season_team_standings(&self, season_id: i64, car_class_id: i64, race_week_num: Option<i64>) -> serde_json::Value {
-        let mut params = HashMap::from([
-            ("season_id", season_id.to_string()),
-            ("car_class_id", car_class_id.to_string()),
-        ]);
-        if let Some(race_week_num) = race_week_num {
-            params.insert("race_week_num", race_week_num.to_string());
+    pub async fn get_season_team_standings(&self, season_id: i64, car_class_id: i64, race_week_num: Option<i64>) -> Option<Vec<serde_json::Value>> {
+        let standings = iracing_api::SeasonStandings{ season_id, car_class_id, race_week_num };
+        return self.fetch(&iracing_api::SeasonTeamStandings(standings)).await;
+    }
+
+    pub async fn get_season_driver_standings(&self, season_id: i64, car_class_id: i64, race_week_num: Option<i64>) -> Option<Vec<serde_json::Value>> {
+        let standings = iracing_api::SeasonStandings{ season_id, car_class_id, race_week_num };
+        return self.fetch(&iracing_api::SeasonDriverStandings(standings)).await;
+    }
+
+    // Standings after each race week, until the first week without standings
+    pub async fn get_weekly_season_team_standings(&self, season_id: i64, car_class_id: i64) -> Vec<Vec<serde_json::Value>> {
+        let mut weekly_standings = Vec::new();
+        loop {
+            let week_num = weekly_standings.len() as i64;
+            match self.get_season_team_standings(season_id, car_class_id, Some(week_num)).await {
+                Some(standings) if !standings.is_empty() => weekly_standings.push(standings),
+                _ => break,
+            }
+        }
+        return weekly_standings;
+    }
+
+    pub async fn get_weekly_season_driver_standings(&self, season_id: i64, car_class_id: i64) -> Vec<Vec<serde_json::Value>> {
+        let mut weekly_standings = Vec::new();
+        loop {
+            let week_num = weekly_standings.len() as i64;
+            match self.get_season_driver_standings(season_id, car_class_id, Some(week_num)).await {
+                Some(standings) if !standings.is_empty() => weekly_standings.push(standings),
+                _ => break,
+            }
+        }
+        return weekly_standings;
+    }
+
+    // Logs in again, through OAuth if a client id is configured, and saves the new session
+    pub async fn auth(&self) -> Result<(), AuthError> {
+        let credentials = iracing_auth::load_credentials()?;
+        match &credentials.oauth_client {
+            Some(oauth_client) => {
+                let token = iracing_auth::oauth_login(&self.client, &credentials, oauth_client).await?;
+                *self.oauth_token.lock().unwrap() = Some(token);
+            },
+            None => {
+                iracing_auth::legacy_login(&self.client, &credentials).await?;
+                *self.oauth_token.lock().unwrap() = None;
+            },
         }
-        return self.get_and_read_chunked2("/data/stats/season_team_standings", &params).await.unwrap_or(serde_json::Value::Null);
+        self.save_session();
+        return Ok(());
     }
 
-    pub async fn auth(&self) {
-        let user = std::env::var("IRACING_USER").unwrap();
-        let token = std::env::var("IRACING_TOKEN").unwrap();
+    // Logs in unless a saved session can be tried first, an expired one is
+    // replaced when the api answers 401
+    pub async fn ensure_session(&self) -> Result<(), AuthError> {
+        let has_cookies = self.cookie_jar.cookies(&Url::parse(BASEURL).unwrap()).is_some();
+        let has_oauth_token = self.oauth_token.lock().unwrap().is_some();
+        if has_cookies || has_oauth_token {
+            return Ok(());
+        }
+        return self.auth().await;
+    }
+
+    fn save_session(&self) {
+        let cookies = self.cookie_jar.cookies(&Url::parse(BASEURL).unwrap())
+            .and_then(|cookies| cookies.to_str().ok().map(str::to_owned));
+        iracing_auth::save_session(&SavedSession{
+            cookies,
+            oauth_token: self.oauth_token.lock().unwrap().clone(),
+        });
+    }
 
-        let body = HashMap::from([
-            ("email", user),
-            ("password", token)
-        ]);
+    // The OAuth access token to send, refreshed first if it is about to expire
+    async fn current_access_token(&self) -> Result<Option<String>, AuthError> {
+        let token = self.oauth_token.lock().unwrap().clone();
+        let Some(token) = token else {
+            return Ok(None);
+        };
+        if token.is_access_token_valid() {
+            return Ok(Some(token.access_token));
+        }
 
-        let response = self.client.post(format!("{}/auth", BASEURL)).json(&body).send().await.unwrap();
-        assert!(response.status() == reqwest::StatusCode::OK);
+        let credentials = iracing_auth::load_credentials()?;
+        let refreshed = match (&credentials.oauth_client, token.valid_refresh_token()) {
+            (Some(oauth_client), Some(refresh_token)) => iracing_auth::oauth_refresh(&self.client, oauth_client, refresh_token).await.ok(),
+            _ => None,
+        };
+        match refreshed {
+            Some(refreshed) => {
+                *self.oauth_token.lock().unwrap() = Some(refreshed);
+                self.save_session();
+            },
+            None => self.auth().await?,
+        }
+        return Ok(self.oauth_token.lock().unwrap().as_ref().map(|token| token.access_token.clone()));
     }
 }
 
-fn filter_non_cached(subsessions: Vec<i64>) -> Vec<i64> {
-    let len1 = subsessions.len();
-    let res: Vec<_> = subsessions.into_iter().filter(|ses| !crate::db::is_session_cached(*ses)).collect();
-    let len2 = res.len();
-    println!("Non-cached sessions {len2}/{len1}");
-    return res;
+// The archive writes and db inserts of a sync block for a while, the server's other
+// requests run on the same async threads. Panics carry over to the caller.
+async fn run_blocking<F: FnOnce() + Send + 'static>(f: F) {
+    if let Err(error) = tokio::task::spawn_blocking(f).await {
+        std::panic::resume_unwind(error.into_panic());
+    }
 }
 
-async fn sync_subsession(client: &mut IRacingClient, subsession_id: i64, prefix: &str) -> bool {
+// false if the subsession can't be downloaded at all
+async fn sync_subsession(client: &IRacingClient, subsession_id: i64, prefix: &str) -> Result<bool, RequestError> {
     if crate::db::is_session_cached(subsession_id) {
-        return true;
+        return Ok(true);
     }
 
     println!("{prefix}Syncing session {subsession_id}");
 
-    if let Some(res) = client.get_subsession(subsession_id).await {
-        crate::db::write_cached_session_json(subsession_id, &res);
-        return true;
+    if let Some(res) = client.try_fetch(&iracing_api::ResultsGet{ subsession_id }, MAX_ATTEMPTS).await? {
+        run_blocking(move || crate::db::write_cached_session_json(subsession_id, &res)).await;
+        return Ok(true);
     }
-    return false;
+    return Ok(false);
 }
 
-async fn sync_subsessions(client: &mut IRacingClient, subsession_ids: &Vec<i64>) -> Vec<i64> {
+// returns the synced subsession ids and the ones that failed to download
+async fn sync_subsessions(client: &IRacingClient, subsession_ids: &Vec<i64>, progress: Option<&ProgressSender>) -> (Vec<i64>, Vec<i64>) {
 
     let len = subsession_ids.len();
     println!("Syncing {len} subsessions");
 
     let mut synced_subsession_ids = Vec::new();
+    let mut failed_subsession_ids = Vec::new();
 
     // Tried concurent stuff. Failed
     // let results = stream::iter(subsession_ids).map(|subsession_id| {
@@ -405,25 +686,53 @@ async fn sync_subsessions(client: &mut IRacingClient, subsession_ids: &Vec<i64>)
     // results.collect::<Vec<()>>().await;
 
     let start = Instant::now();
+    let mut batch = Vec::new();
+    report_progress(progress, "downloading", 0, len);
     for (i, subsession_id) in subsession_ids.into_iter().enumerate() {
         let elapsed_secs = start.elapsed().as_secs_f32();
         let rate = i as f32 / elapsed_secs;
         let ip1 = i+1;
 
         // This can fail if we don't have permission to view the subsession
-        let success = sync_subsession(client, *subsession_id, format!("{ip1}/{len} {rate:.2}/s ").as_str()).await;
-        if success {
-            synced_subsession_ids.push(*subsession_id);
+        match sync_subsession(client, *subsession_id, format!("{ip1}/{len} {rate:.2}/s ").as_str()).await {
+            Ok(true) => {
+                synced_subsession_ids.push(*subsession_id);
+                batch.push(*subsession_id);
+            },
+            Ok(false) => {},
+            Err(error) => {
+                println!("Failed to download session {subsession_id}, request {error}");
+                failed_subsession_ids.push(*subsession_id);
+            },
         }
+        if batch.len() >= SUBSESSION_BATCH_SIZE {
+            let full_batch = std::mem::take(&mut batch);
+            run_blocking(move || add_subsessions_to_db(&full_batch)).await;
+        }
+        report_progress(progress, "downloading", ip1, len);
     }
+    run_blocking(move || add_subsessions_to_db(&batch)).await;
+    return (synced_subsession_ids, failed_subsession_ids);
+}
+
+// Downloads the subsessions that are not cached yet and adds them to the db, batch by batch
+pub async fn sync_subsessions_to_db(client: &IRacingClient, subsession_ids: Vec<i64>, progress: Option<&ProgressSender>) -> Vec<i64> {
+    let (synced_subsession_ids, _failed_subsession_ids) = try_sync_subsessions_to_db(client, subsession_ids, progress).await;
     return synced_subsession_ids;
 }
 
-pub async fn sync_subsessions_to_db(client: &mut IRacingClient, subsession_ids: Vec<i64>) -> Vec<i64> {
-    let synced_subsession_ids = sync_subsessions(client, &filter_non_cached(subsession_ids)).await;
+// Like sync_subsessions_to_db, also returning the ids that failed to download
+async fn try_sync_subsessions_to_db(client: &IRacingClient, subsession_ids: Vec<i64>, progress: Option<&ProgressSender>) -> (Vec<i64>, Vec<i64>) {
+    // cached by an interrupted sync, but never added to the db
+    let (cached, non_cached): (Vec<i64>, Vec<i64>) = subsession_ids.into_iter().partition(|subsession_id| crate::db::is_session_cached(*subsession_id));
+    let cached_not_in_db = crate::db::query_subsession_ids_not_in_db(&crate::db::create_db_connection(), &cached);
+    if !cached_not_in_db.is_empty() {
+        println!("Adding {} cached subsessions missing from the db", cached_not_in_db.len());
+        run_blocking(move || add_subsessions_to_db(&cached_not_in_db)).await;
+    }
 
-    add_subsessions_to_db(&synced_subsession_ids);
-    return synced_subsession_ids;
+    println!("Non-cached sessions {}/{}", non_cached.len(), non_cached.len() + cached.len());
+    return sync_subsessions(client, &non_cached, progress).await;
 }
 
 fn add_subsessions_to_db(subsession_ids: &Vec<i64>) {
@@ -436,60 +745,238 @@ fn add_subsessions_to_db(subsession_ids: &Vec<i64>) {
             crate::db::add_session_to_db_from_cache(&mut ctx, *subsession_id);
         }
     }
+    if !subsession_ids.is_empty() {
+        crate::db::bump_data_version(&tx);
+    }
+    crate::db::set_last_sync_time(&tx);
 
     tx.commit().unwrap();
 }
 
-pub async fn sync_track_infos_to_db(client: &mut IRacingClient) {
-    let data = client.get_and_read("/data/track/get", &HashMap::new()).await.unwrap();
+// iRacing occasionally amends results (penalties, DQs) after the fact, so cached
+// subsessions from the last `days` days are downloaded again and replaced if changed
+pub async fn refresh_recent_subsessions_in_db(client: &IRacingClient, days: u64) -> Vec<i64> {
+    let since = Utc::now().checked_sub_days(Days::new(days)).unwrap();
+
+    let con = crate::db::create_db_connection();
+    let subsession_ids = query_subsession_ids_since(&con, since.format("%Y-%m-%d %H:%M:%S").to_string());
+
+    let len = subsession_ids.len();
+    println!("Refreshing {len} subsessions since {since}");
+
+    let mut changed_subsessions = Vec::new();
+    for (i, subsession_id) in subsession_ids.iter().enumerate() {
+        let ip1 = i+1;
+        println!("{ip1}/{len} Refreshing session {subsession_id}");
+
+        let Some(new_json) = client.get_subsession(*subsession_id).await else {
+            continue;
+        };
+
+        if crate::db::is_session_cached(*subsession_id) {
+            let old_json = crate::db::read_cached_session_json(*subsession_id);
+            let changes = diff_subsessions(&old_json, &new_json);
+            if changes.is_empty() {
+                continue;
+            }
+            println!("Subsession {subsession_id} changed:");
+            for change in changes {
+                println!("  {change}");
+            }
+        }
+
+        crate::db::write_cached_session_json(*subsession_id, &new_json);
+        changed_subsessions.push(new_json);
+    }
+
+    crate::db::replace_sessions_in_db(&changed_subsessions);
+
+    let changed_len = changed_subsessions.len();
+    println!("{changed_len}/{len} subsessions changed");
+
+    return changed_subsessions.iter().map(|ses| ses["subsession_id"].as_i64().unwrap()).collect();
+}
+
+pub async fn sync_track_infos_to_db(client: &IRacingClient) {
+    let data = client.fetch(&iracing_api::TrackGet).await.unwrap();
     crate::db::write_cached_track_infos_json(&data);
     crate::db::rebuild_tracks_in_db();
 }
 
-pub async fn sync_car_infos_to_db(client: &mut IRacingClient) {
-    let data = client.get_and_read("/data/car/get", &HashMap::new()).await.unwrap();
+pub async fn sync_car_infos_to_db(client: &IRacingClient) {
+    let data = client.fetch(&iracing_api::CarGet).await.unwrap();
     crate::db::write_cached_car_infos_json(&data);
     crate::db::rebuild_cars_in_db();
 }
 
-pub async fn sync_car_class_infos_to_db(client: &mut IRacingClient) {
-    let data = client.get_and_read("/data/carclass/get", &HashMap::new()).await.unwrap();
+pub async fn sync_car_class_infos_to_db(client: &IRacingClient) {
+    let data = client.fetch(&iracing_api::CarClassGet).await.unwrap();
     crate::db::write_cached_car_class_infos_json(&data);
     crate::db::rebuild_car_classes_in_db();
 }
 
-pub async fn sync_season_infos_to_db(client: &mut IRacingClient) {
+pub async fn sync_season_infos_to_db(client: &IRacingClient) {
     let data = client.get_all_season_list().await;
     crate::db::write_cached_seasons_json(&data);
     crate::db::rebuild_seasons_in_db();
 }
 
-pub async fn sync_site_teams_to_db(client: &mut IRacingClient, partial: bool) -> Vec<i64> {
+pub async fn sync_season_schedules_to_db(client: &IRacingClient) {
+    let data = client.fetch(&iracing_api::SeriesSeasons{ include_series: true }).await.unwrap();
+    crate::db::write_cached_season_schedules_json(&data);
+    crate::db::rebuild_season_schedules_in_db();
+}
+
+// Standings of every official series site team members raced in recently
+pub async fn sync_standings_to_db(client: &IRacingClient, days: i64) {
+    let con = crate::db::create_db_connection();
+    let start_date = to_api_date_string(&(Utc::now() - chrono::Duration::days(days)));
+    let season_car_classes = crate::db::query_site_team_season_car_classes(&con, start_date);
+    let member_cust_ids: HashSet<i64> = query_all_site_team_members(&con).into_iter().collect();
+
+    for (i, (season_id, car_class_id)) in season_car_classes.iter().enumerate() {
+        println!("Syncing standings {}/{}: season {} car class {}", i + 1, season_car_classes.len(), season_id, car_class_id);
+
+        let weekly_driver_standings = client.get_weekly_season_driver_standings(*season_id, *car_class_id).await;
+        let weekly_team_standings = client.get_weekly_season_team_standings(*season_id, *car_class_id).await;
+
+        crate::db::replace_season_standings_in_db(
+            *season_id,
+            *car_class_id,
+            &weekly_driver_standings,
+            &weekly_team_standings,
+            &member_cust_ids);
+    }
+}
+
+// iRacing's profile and career stats of the site team members, to show next to the stats
+// computed from the db. Official starts the db doesn't have are reported, they usually
+// mean a search missed sessions.
+pub async fn sync_member_stats_to_db(client: &IRacingClient) {
+    let con = crate::db::create_db_connection();
+    let cust_ids = query_all_site_team_members(&con);
+
+    for (i, cust_id) in cust_ids.iter().enumerate() {
+        println!("Syncing member stats {}/{}: {}", i + 1, cust_ids.len(), cust_id);
+
+        let profile = client.get_member_profile(*cust_id).await;
+        let career = client.get_member_career_stats(*cust_id).await;
+        let yearly = client.get_member_yearly_stats(*cust_id).await;
+        let (Some(profile), Some(career), Some(yearly)) = (profile, career, yearly) else {
+            println!("Skipping {cust_id}, iRacing did not return all of the member stats");
+            continue;
+        };
+
+        crate::db::write_cached_member_stats_json(*cust_id, &serde_json::json!({
+            "cust_id": cust_id,
+            "synced_at": Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            "profile": profile,
+            "career": career,
+            "yearly": yearly,
+        }));
+        crate::db::replace_member_stats_in_db(*cust_id);
+
+        let computed_stats = crate::db::query_member_computed_stats(&con, *cust_id, false);
+        for official in crate::db::query_member_official_stats(&con, *cust_id).iter().filter(|stats| stats.year.is_none()) {
+            let computed_starts = computed_stats.iter()
+                .find(|computed| computed.category_id == official.category_id)
+                .map_or(0, |computed| computed.starts);
+            if computed_starts < official.starts {
+                println!("{cust_id} has {} official starts in category {}, the db has {computed_starts}", official.starts, official.category_id);
+            }
+        }
+    }
+}
+
+// Splits the members into ones whose history in the db reaches back to when they joined
+// iRacing (or close enough for a partial sync to cover the rest) and ones that need a full
+// sync, e.g. because they were just added to a site team. Members whose history is known to
+// be complete are not checked again.
+async fn check_member_histories(client: &IRacingClient, cust_ids: &Vec<i64>) -> (Vec<(i64, String)>, Vec<i64>) {
+    let con = crate::db::create_db_connection();
+    let mut newly_complete = Vec::new();
+    let mut incomplete = Vec::new();
+
+    for cust_id in cust_ids {
+        if crate::db::query_driver_history_complete(&con, *cust_id) {
+            continue;
+        }
+        let member_since = client.get_member_since_date(*cust_id).await;
+        let earliest_session = match crate::db::query_earliest_session_start_time(&con, *cust_id) {
+            Some(start_time) => NaiveDate::parse_from_str(&start_time[..10], "%Y-%m-%d").unwrap(),
+            None => Utc::now().date_naive(),
+        };
+
+        if earliest_session - member_since.date_naive() <= chrono::Duration::days(PARTIAL_SYNC_DAYS as i64) {
+            newly_complete.push((*cust_id, member_since.format("%Y-%m-%d").to_string()));
+        } else {
+            println!("{cust_id} is a member since {}, but the db only goes back to {earliest_session}, syncing every session", member_since.date_naive());
+            incomplete.push(*cust_id);
+        }
+    }
+    return (newly_complete, incomplete);
+}
+
+pub async fn sync_site_teams_to_db(client: &IRacingClient, partial: bool) -> Vec<i64> {
     let mut con = crate::db::create_db_connection();
     let cust_ids = query_all_site_team_members(&mut con);
     if partial {
-        return sync_cust_ids_to_db(client, &Vec::new(), &cust_ids).await;
+        let (newly_complete, incomplete) = check_member_histories(client, &cust_ids).await;
+        // Everyone's recent sessions are what the discord update posts. The backfill of the
+        // incomplete histories runs on its own, its old sessions are not news.
+        let subsession_ids = sync_cust_ids_to_db(client, &Vec::new(), &cust_ids, None).await;
+        if !incomplete.is_empty() {
+            sync_cust_ids_to_db(client, &incomplete, &Vec::new(), None).await;
+        }
+        for (cust_id, member_since) in newly_complete {
+            crate::db::set_driver_history_complete(&con, cust_id, Some(member_since));
+        }
+        return subsession_ids;
     } else {
-        return sync_cust_ids_to_db(client, &cust_ids, &Vec::new()).await;
+        return sync_cust_ids_to_db(client, &cust_ids, &Vec::new(), None).await;
     }
 }
 
-pub async fn sync_cust_ids_to_db(client: &mut IRacingClient, cust_ids: &Vec<i64>, cust_ids_partial: &Vec<i64>) -> Vec<i64> {
+pub async fn sync_cust_ids_to_db(client: &IRacingClient, cust_ids: &Vec<i64>, cust_ids_partial: &Vec<i64>, progress: Option<&ProgressSender>) -> Vec<i64> {
     let mut subsession_ids = HashSet::<i64>::new();
-
-    for cust_id in cust_ids {
-        subsession_ids.extend(client.find_subsessions_for_driver(*cust_id, false).await);
-    }
-    for cust_id in cust_ids_partial {
-        subsession_ids.extend(client.find_subsessions_for_driver(*cust_id, true).await);
+    // per driver, whether the search completed and what it found
+    let mut driver_searches = Vec::new();
+    // every search reaches past this
+    let searched_until = to_api_date_string(&Utc::now());
+
+    let searches: Vec<(i64, bool)> = cust_ids.iter().map(|cust_id| (*cust_id, false))
+        .chain(cust_ids_partial.iter().map(|cust_id| (*cust_id, true)))
+        .collect();
+    report_progress(progress, "searching", 0, searches.len());
+    for (i, (cust_id, partial)) in searches.iter().enumerate() {
+        let (driver_subsession_ids, complete) = client.find_subsessions_for_driver(*cust_id, *partial).await;
+        subsession_ids.extend(&driver_subsession_ids);
+        driver_searches.push((*cust_id, *partial, complete, driver_subsession_ids));
+        report_progress(progress, "searching", i + 1, searches.len());
     }
 
     let subsession_ids_vec = Vec::from_iter(subsession_ids.into_iter());
 
-    return sync_subsessions_to_db(client, subsession_ids_vec).await;
+    let (synced_subsession_ids, failed_subsession_ids) = try_sync_subsessions_to_db(client, subsession_ids_vec, progress).await;
+    let failed_subsession_ids: HashSet<i64> = HashSet::from_iter(failed_subsession_ids);
+
+    // recorded only once the found sessions are in the db, so a failed sync searches the same range again
+    let con = crate::db::create_db_connection();
+    for (cust_id, partial, complete, driver_subsession_ids) in &driver_searches {
+        if !complete || driver_subsession_ids.iter().any(|subsession_id| failed_subsession_ids.contains(subsession_id)) {
+            println!("Not all sessions of {cust_id} were synced, the next sync searches the same range again");
+            continue;
+        }
+        if !partial {
+            crate::db::set_driver_history_complete(&con, *cust_id, None);
+            crate::db::clear_backfill_checkpoints(&con, *cust_id);
+        }
+        crate::db::set_driver_searched_until(&con, *cust_id, &searched_until);
+    }
+    return synced_subsession_ids;
 }
 
-pub async fn sync_drivers_to_db(client: &mut IRacingClient, driver_names: &Vec<String>, driver_names_partial: &Vec<String>) {
+pub async fn sync_drivers_to_db(client: &IRacingClient, driver_names: &Vec<String>, driver_names_partial: &Vec<String>) {
     let mut cust_ids = Vec::new();
     let mut cust_ids_partial = Vec::new();
 
@@ -503,10 +990,12 @@ pub async fn sync_drivers_to_db(client: &mut IRacingClient, driver_names: &Vec<S
         println!("{driver_name} -> {cust_id}");
         cust_ids_partial.push(cust_id)
     }
-    sync_cust_ids_to_db(client, &cust_ids, &cust_ids_partial).await;
+    sync_cust_ids_to_db(client, &cust_ids, &cust_ids_partial, None).await;
 }
 
-pub async fn sync_season_to_db(client: &mut IRacingClient, year: i32, quarter: i32, week: Option<i32>) {
+pub async fn sync_season_to_db(client: &IRacingClient, year: i32, quarter: i32, week: Option<i32>, progress: Option<&ProgressSender>) {
+    report_progress(progress, "searching", 0, 1);
     let subsession_ids = client.find_subsessions_for_season(year, quarter, week).await;
-    sync_subsessions_to_db(client, subsession_ids).await;
+    report_progress(progress, "searching", 1, 1);
+    sync_subsessions_to_db(client, subsession_ids, progress).await;
 }
\ No newline at end of file
@@ -5,6 +5,8 @@ mod server_logger;
 mod schema;
 mod db;
 mod iracing_client;
+mod iracing_auth;
+mod iracing_api;
 mod category_type;
 mod event_type;
 mod simsession_type;
@@ -14,12 +16,20 @@ mod discord_bot;
 mod discord_hook;
 mod dirs;
 mod sof_calculator;
+mod icalendar;
+mod session_archive;
+mod subsession_diff;
+mod api_types;
+mod report_format;
+mod xlsx;
+mod etag;
+mod metrics;
+mod auth;
+mod sync_jobs;
 
 use clap::Parser;
 use std::collections::HashMap;
 use std::fs;
-use sha2::Digest;
-use sha2::Sha256;
 
 #[derive(Parser)]
 struct Args {
@@ -31,14 +41,46 @@ struct Args {
     #[arg(long)]
     rebuild_db_schema: bool,
 
-    /// Rebuild site teams
+    /// Replace the site teams in the database with static-data/site-teams.json, dropping changes made through the api
     #[arg(long)]
     rebuild_site_teams: bool,
 
+    /// Write the site teams of the database to a file in the static-data/site-teams.json format
+    #[arg(long, value_name = "FILE")]
+    export_site_teams: Option<String>,
+
     /// Add missing cached sessions to the database
     #[arg(short, long)]
     update_db: bool,
 
+    /// Create an api token with the given name and print it, see --api-token-role
+    #[arg(long, value_name = "NAME")]
+    create_api_token: Option<String>,
+
+    /// Role of the token created by --create-api-token: viewer, team-manager or admin
+    #[arg(long, default_value = "viewer")]
+    api_token_role: String,
+
+    /// Site team a team-manager token created by --create-api-token manages
+    #[arg(long)]
+    api_token_site_team: Option<String>,
+
+    /// Revoke the api token with the given name
+    #[arg(long, value_name = "NAME")]
+    revoke_api_token: Option<String>,
+
+    /// Summarise the most requested drivers and teams from the server request logs
+    #[arg(long)]
+    log_report: bool,
+
+    /// Number of entries per list in --log-report
+    #[arg(long, default_value_t = 20)]
+    log_report_top: usize,
+
+    /// Move cached sessions from one zip per subsession into the packed archive
+    #[arg(long)]
+    convert_session_cache: bool,
+
     /// Sync driver to db
     #[arg(short = 'D', long)]
     sync_drivers_to_db: Vec<String>,
@@ -59,6 +101,10 @@ struct Args {
     #[arg(short = 'c', long)]
     sync_cust_ids_to_db_partial: Vec<i64>,
 
+    /// Re-download subsessions from the last N days and replace the ones iRacing amended
+    #[arg(long, value_name = "DAYS")]
+    refresh_recent: Option<u64>,
+
     /// Sync site teams to db
     #[arg(long)]
     sync_site_teams_to_db: bool,
@@ -79,6 +125,18 @@ struct Args {
     #[arg(short = 's', long)]
     sync_season_infos_to_db: bool,
 
+    /// Sync the weekly schedules of the current seasons
+    #[arg(long)]
+    sync_season_schedules_to_db: bool,
+
+    /// Sync the weekly standings of official series site team members recently raced in
+    #[arg(long)]
+    sync_standings_to_db: bool,
+
+    /// Sync the iRacing profile and career stats of the site team members
+    #[arg(long)]
+    sync_member_stats_to_db: bool,
+
     /// Sync season year to db
     #[arg(short = 'y', long)]
     season_year: Option<i32>,
@@ -111,7 +169,7 @@ struct Args {
     #[arg(long)]
     test_send_discord_update: bool,
 
-    /// Generate iracing token
+    /// Generate iracing token (the masked password, for IRACING_TOKEN)
     #[arg(long)]
     generate_iracing_token: bool,
 
@@ -123,8 +181,9 @@ struct Args {
     #[arg(long)]
     gen_pw: Option<String>,
 
-    /// Query iracing API
-    #[arg(long)]
+    /// Query iracing API and print the data, e.g. "/data/member/get?cust_ids=1,2".
+    /// Links and chunked responses are followed whatever the endpoint
+    #[arg(long, value_name = "PATH")]
     query_iracing_api: Option<String>,
 }
 
@@ -135,12 +194,16 @@ fn has_async(args: &Args) -> bool {
         !args.sync_drivers_to_db_partial.is_empty() ||
         !args.sync_cust_ids_to_db_partial.is_empty() ||
         !args.sync_subsession_ids_to_db.is_empty() ||
+        args.refresh_recent.is_some() ||
         args.sync_site_teams_to_db ||
         args.sync_site_teams_to_db_partial ||
         args.season_year.is_some() ||
         args.sync_car_infos_to_db ||
         args.sync_track_infos_to_db ||
         args.sync_season_infos_to_db ||
+        args.sync_season_schedules_to_db ||
+        args.sync_standings_to_db ||
+        args.sync_member_stats_to_db ||
         args.test_send_discord_update ||
         args.query_iracing_api.is_some()
 }
@@ -150,24 +213,31 @@ async fn tokio_main(args: &Args) {
         return;
     }
 
-    let mut client = iracing_client::IRacingClient::new();
+    let client = iracing_client::IRacingClient::new();
 
-    client.auth().await;
+    if let Err(error) = client.ensure_session().await {
+        println!("Could not log in to iRacing: {error}");
+        return;
+    }
 
     if !args.sync_drivers_to_db.is_empty() || !args.sync_drivers_to_db_partial.is_empty() {
-        iracing_client::sync_drivers_to_db(&mut client, &args.sync_drivers_to_db, &args.sync_drivers_to_db_partial).await;
+        iracing_client::sync_drivers_to_db(&client, &args.sync_drivers_to_db, &args.sync_drivers_to_db_partial).await;
     }
 
     if !args.sync_cust_ids_to_db.is_empty() || !args.sync_cust_ids_to_db_partial.is_empty() {
-        iracing_client::sync_cust_ids_to_db(&mut client, &args.sync_cust_ids_to_db, &args.sync_cust_ids_to_db_partial).await;
+        iracing_client::sync_cust_ids_to_db(&client, &args.sync_cust_ids_to_db, &args.sync_cust_ids_to_db_partial, None).await;
     }
 
     if !args.sync_subsession_ids_to_db.is_empty() {
-        iracing_client::sync_subsessions_to_db(&mut client, args.sync_subsession_ids_to_db.clone()).await;
+        iracing_client::sync_subsessions_to_db(&client, args.sync_subsession_ids_to_db.clone(), None).await;
+    }
+
+    if let Some(days) = args.refresh_recent {
+        iracing_client::refresh_recent_subsessions_in_db(&client, days).await;
     }
 
     if args.sync_site_teams_to_db {
-        iracing_client::sync_site_teams_to_db(&mut client, false).await;
+        iracing_client::sync_site_teams_to_db(&client, false).await;
     }
 
     if args.test_send_discord_update {
@@ -180,56 +250,71 @@ async fn tokio_main(args: &Args) {
     }
 
     if args.sync_site_teams_to_db_partial {
-        let subsession_ids = iracing_client::sync_site_teams_to_db(&mut client, true).await;
+        let subsession_ids = iracing_client::sync_site_teams_to_db(&client, true).await;
         if args.send_discord_update {
             discord_hook::send_discord_update(subsession_ids, false).await;
         }
     }
 
     if args.season_year.is_some() && args.season_quarter.is_some() {
-        iracing_client::sync_season_to_db(&mut client,
-            args.season_year.unwrap(), args.season_quarter.unwrap(), args.season_week).await;
+        iracing_client::sync_season_to_db(&client,
+            args.season_year.unwrap(), args.season_quarter.unwrap(), args.season_week, None).await;
     }
 
     if args.sync_car_infos_to_db {
-        iracing_client::sync_car_infos_to_db(&mut client).await;
-        iracing_client::sync_car_class_infos_to_db(&mut client).await;
+        iracing_client::sync_car_infos_to_db(&client).await;
+        iracing_client::sync_car_class_infos_to_db(&client).await;
     }
 
     if args.sync_track_infos_to_db {
-        iracing_client::sync_track_infos_to_db(&mut client).await;
+        iracing_client::sync_track_infos_to_db(&client).await;
     }
 
     if args.sync_season_infos_to_db {
-        iracing_client::sync_season_infos_to_db(&mut client).await;
+        iracing_client::sync_season_infos_to_db(&client).await;
     }
 
-    if let Some(suffix) = &args.query_iracing_api {
-        // TODO maybe create a get_and_read_smart that can determine which kind of reader to use
-        let json = client.get_and_read_chunked(&suffix, &HashMap::new()).await;
-        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+    if args.sync_season_schedules_to_db {
+        iracing_client::sync_season_schedules_to_db(&client).await;
     }
-}
 
-fn encode_iracing_pw(password: &str, identifier: &str) -> String {
-    let mut hasher = Sha256::new();
-    let normalized = identifier.trim().to_lowercase();
+    if args.sync_standings_to_db {
+        iracing_client::sync_standings_to_db(&client, iracing_client::STANDINGS_SYNC_DAYS).await;
+    }
 
-    hasher.update(format!("{password}{normalized}"));
-    base64::encode(hasher.finalize())
-}
+    if args.sync_member_stats_to_db {
+        iracing_client::sync_member_stats_to_db(&client).await;
+    }
 
+    if let Some(query) = &args.query_iracing_api {
+        let (suffix, query_params) = query.split_once('?').unwrap_or((query, ""));
+        let params: HashMap<&str, String> = query_params.split('&')
+            .filter_map(|param| param.split_once('='))
+            .map(|(name, value)| (name, value.to_owned()))
+            .collect();
+        let json = client.get_and_read_smart(suffix, &params).await;
+        if let Some((_, shape)) = &json {
+            // stderr, so the data can be piped
+            eprintln!("Response shape: {shape}");
+        }
+        println!("{}", serde_json::to_string_pretty(&json.map(|(json, _)| json)).unwrap());
+    }
+}
 
 #[rocket::main]
 async fn main() {
     let args = Args::parse();
 
     fs::create_dir_all(crate::db::get_sessions_dir()).unwrap();
+    fs::create_dir_all(crate::db::get_session_packs_dir()).unwrap();
 
     if args.motec_thing {
         crate::motec_xml::output_motec_track_xmls2();
         crate::motec_xml::output_motec_car_xmls2();
     }
+    if args.convert_session_cache {
+        db::convert_session_cache_to_archive();
+    }
     if args.rebuild_db_schema {
         db::rebuild_db_schema();
     }
@@ -239,11 +324,31 @@ async fn main() {
     if args.rebuild_site_teams {
         db::rebuild_site_teams_in_db();
     }
+    if let Some(path) = &args.export_site_teams {
+        db::write_site_teams_data_file(std::path::Path::new(path));
+    }
     if args.update_db {
         db::update_db();
     }
+    if let Some(name) = &args.create_api_token {
+        let Some(role) = auth::Role::from_str(&args.api_token_role) else {
+            panic!("Unknown api token role {}", args.api_token_role);
+        };
+        match auth::create_api_token(name, role, args.api_token_site_team.clone()) {
+            Ok(token) => println!("{token}"),
+            Err(error) => println!("{error}"),
+        }
+    }
+    if let Some(name) = &args.revoke_api_token {
+        if !db::remove_api_token_from_db(name) {
+            println!("No api token named {name}");
+        }
+    }
+    if args.log_report {
+        server_logger::print_log_report(&server_logger::get_log_file_path(), args.log_report_top);
+    }
     if args.generate_iracing_token {
-        println!("{}", encode_iracing_pw(args.gen_pw.clone().unwrap().as_str(), args.gen_email.clone().unwrap().as_str()));
+        println!("{}", iracing_auth::mask_secret(args.gen_pw.clone().unwrap().as_str(), args.gen_email.clone().unwrap().as_str()));
     }
     if args.start_server {
         crate::server::start_rocket_server(args.enable_https).await;
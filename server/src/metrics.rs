@@ -0,0 +1,126 @@
+// Request metrics of the server, rendered in the Prometheus text format by /metrics.
+//
+// `Metrics` is managed state, the `RequestMetrics` fairing fills it in. Requests are
+// counted per route (the mount pattern, not the concrete URI) so the number of
+// series stays bounded.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+
+// upper bounds in seconds
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0];
+
+#[derive(Default)]
+struct RouteLatency {
+    // cumulative, bucket_counts[i] counts requests taking at most LATENCY_BUCKETS[i]
+    bucket_counts: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct MetricsData {
+    // (method, route, status) -> count
+    request_counts: BTreeMap<(String, String, u16), u64>,
+    // (method, route) -> latency
+    request_latencies: BTreeMap<(String, String), RouteLatency>,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    data: Mutex<MetricsData>,
+}
+
+// Gauges that are read at scrape time instead of being tracked by the fairing
+pub struct Gauge {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub value: f64,
+}
+
+fn escape_label(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+}
+
+impl Metrics {
+    fn record(&self, method: String, route: String, status: u16, seconds: f64) {
+        let mut data = self.data.lock().unwrap();
+        *data.request_counts.entry((method.clone(), route.clone(), status)).or_insert(0) += 1;
+
+        let latency = data.request_latencies.entry((method, route)).or_default();
+        for (i, bound) in LATENCY_BUCKETS.iter().enumerate() {
+            if seconds <= *bound {
+                latency.bucket_counts[i] += 1;
+            }
+        }
+        latency.count += 1;
+        latency.sum += seconds;
+    }
+
+    pub fn render(&self, gauges: &Vec<Gauge>) -> String {
+        let data = self.data.lock().unwrap();
+        let mut output = String::new();
+
+        writeln!(output, "# HELP iracing_stats_http_requests_total Number of handled HTTP requests").unwrap();
+        writeln!(output, "# TYPE iracing_stats_http_requests_total counter").unwrap();
+        for ((method, route, status), count) in data.request_counts.iter() {
+            let route = escape_label(route);
+            writeln!(output, "iracing_stats_http_requests_total{{method=\"{method}\",route=\"{route}\",status=\"{status}\"}} {count}").unwrap();
+        }
+
+        writeln!(output, "# HELP iracing_stats_http_request_duration_seconds Time to handle HTTP requests").unwrap();
+        writeln!(output, "# TYPE iracing_stats_http_request_duration_seconds histogram").unwrap();
+        for ((method, route), latency) in data.request_latencies.iter() {
+            let route = escape_label(route);
+            let labels = format!("method=\"{method}\",route=\"{route}\"");
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(latency.bucket_counts.iter()) {
+                writeln!(output, "iracing_stats_http_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {count}").unwrap();
+            }
+            writeln!(output, "iracing_stats_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}", latency.count).unwrap();
+            writeln!(output, "iracing_stats_http_request_duration_seconds_sum{{{labels}}} {}", latency.sum).unwrap();
+            writeln!(output, "iracing_stats_http_request_duration_seconds_count{{{labels}}} {}", latency.count).unwrap();
+        }
+
+        for gauge in gauges {
+            writeln!(output, "# HELP {} {}", gauge.name, gauge.help).unwrap();
+            writeln!(output, "# TYPE {} gauge", gauge.name).unwrap();
+            writeln!(output, "{} {}", gauge.name, gauge.value).unwrap();
+        }
+
+        return output;
+    }
+}
+
+struct RequestStart(Instant);
+
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        return Info {
+            name: "RequestMetrics",
+            kind: Kind::Request | Kind::Response,
+        };
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(metrics) = request.rocket().state::<Metrics>() else {
+            return;
+        };
+        let start = request.local_cache(|| RequestStart(Instant::now()));
+        let route = match request.route() {
+            Some(route) => route.uri.path().to_string(),
+            None => "unmatched".to_owned(),
+        };
+        metrics.record(request.method().to_string(), route, response.status().code, start.0.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,149 @@
+// Content negotiation for the report endpoints. A report can be rendered as
+// JSON (the default), CSV or newline delimited JSON, picked by the `format`
+// query parameter or else by the Accept header.
+
+use std::io::Cursor;
+use rocket::http::{ContentType, MediaType, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use serde::Serialize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    Ndjson,
+}
+
+impl ReportFormat {
+    pub fn from_param(format: &str) -> Option<Self> {
+        return match format {
+            "json" => Some(ReportFormat::Json),
+            "csv" => Some(ReportFormat::Csv),
+            "ndjson" => Some(ReportFormat::Ndjson),
+            _ => None,
+        };
+    }
+
+    fn from_media_type(media_type: &MediaType) -> Option<Self> {
+        return match (media_type.top().as_str(), media_type.sub().as_str()) {
+            ("application", "json") => Some(ReportFormat::Json),
+            ("text", "csv") => Some(ReportFormat::Csv),
+            ("application", "x-ndjson") | ("application", "ndjson") => Some(ReportFormat::Ndjson),
+            _ => None,
+        };
+    }
+
+    fn content_type(&self) -> ContentType {
+        return match self {
+            ReportFormat::Json => ContentType::JSON,
+            ReportFormat::Csv => ContentType::new("text", "csv").with_params(("charset", "utf-8")),
+            ReportFormat::Ndjson => ContentType::new("application", "x-ndjson"),
+        };
+    }
+}
+
+// The format of the Accept header we can produce with the highest q-value, the first
+// one of those on a tie. Formats with q=0 are not acceptable.
+pub struct AcceptedFormat(Option<ReportFormat>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AcceptedFormat {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let mut best: Option<(ReportFormat, f32)> = None;
+        for media_type in request.accept().iter().flat_map(|accept| accept.iter()) {
+            let weight = media_type.weight_or(1.0);
+            let Some(format) = ReportFormat::from_media_type(media_type.media_type()) else {
+                continue;
+            };
+            if weight > 0.0 && best.is_none_or(|(_, best_weight)| weight > best_weight) {
+                best = Some((format, weight));
+            }
+        }
+        return Outcome::Success(AcceptedFormat(best.map(|(format, _)| format)));
+    }
+}
+
+// `format` wins over the Accept header, `default` is used when neither picks one.
+// Err if `format` is not a known format.
+pub fn negotiate_format(format: Option<String>, accepted: &AcceptedFormat, default: ReportFormat) -> Result<ReportFormat, Status> {
+    if let Some(format) = format {
+        return ReportFormat::from_param(&format).ok_or(Status::BadRequest);
+    }
+    return Ok(accepted.0.unwrap_or(default));
+}
+
+// Implemented by report bodies to tell which rows the CSV and NDJSON renderings are made of.
+// A default row is only used to get the CSV header of empty reports.
+pub trait ReportRows {
+    type Row: Serialize + Default;
+    fn rows(&self) -> &Vec<Self::Row>;
+}
+
+impl<T: Serialize + Default> ReportRows for Vec<T> {
+    type Row = T;
+    fn rows(&self) -> &Vec<T> {
+        return self;
+    }
+}
+
+pub struct Report<T> {
+    pub format: ReportFormat,
+    pub body: T,
+    pub csv_headers: bool,
+}
+
+impl<T> Report<T> {
+    pub fn new(format: ReportFormat, body: T) -> Self {
+        return Report{ format, body, csv_headers: true };
+    }
+
+    pub fn without_csv_headers(mut self) -> Self {
+        self.csv_headers = false;
+        return self;
+    }
+}
+
+fn render_csv<R: Serialize + Default>(rows: &Vec<R>, headers: bool) -> Vec<u8> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(headers)
+        .from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row).unwrap();
+    }
+
+    // the csv crate writes the header along with the first row
+    if headers && rows.is_empty() {
+        writer.serialize(R::default()).unwrap();
+        let output = writer.into_inner().unwrap();
+        let header_end = output.iter().position(|byte| *byte == b'\n').unwrap() + 1;
+        return output[..header_end].to_vec();
+    }
+    return writer.into_inner().unwrap();
+}
+
+fn render_ndjson<R: Serialize>(rows: &Vec<R>) -> Vec<u8> {
+    let mut output = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut output, row).unwrap();
+        output.push(b'\n');
+    }
+    return output;
+}
+
+impl<'r, T: Serialize + ReportRows> Responder<'r, 'static> for Report<T> {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        let body = match self.format {
+            ReportFormat::Json => serde_json::to_vec(&self.body).unwrap(),
+            ReportFormat::Csv => render_csv(self.body.rows(), self.csv_headers),
+            ReportFormat::Ndjson => render_ndjson(self.body.rows()),
+        };
+
+        return Response::build()
+            .header(self.format.content_type())
+            .sized_body(body.len(), Cursor::new(body))
+            .ok();
+    }
+}
@@ -34,6 +34,44 @@ pub enum Season {
     DriverChanges,
 }
 
+#[derive(Iden)]
+pub enum SeasonSchedule {
+    Table,
+    SeasonId,
+    SeriesId,
+    RaceWeekNum, // 0 based
+    TrackId,
+    StartDate,
+    RaceLapLimit,
+    RaceTimeLimit, // minutes
+}
+
+#[derive(Iden)]
+pub enum SeasonDriverStanding {
+    Table,
+    SeasonId,
+    CarClassId,
+    RaceWeekNum, // 0 based
+    CustId,
+    Division,
+    Position, // 1 based
+    Points,
+    Starts,
+    Wins,
+}
+
+#[derive(Iden)]
+pub enum SeasonTeamStanding {
+    Table,
+    SeasonId,
+    CarClassId,
+    RaceWeekNum, // 0 based
+    TeamId,
+    TeamName,
+    Position, // 1 based
+    Points,
+}
+
 #[derive(Iden)]
 pub enum Session {
     Table,
@@ -161,6 +199,13 @@ pub enum SiteTeam {
     TeamReportDiscordHookUrl,
 }
 
+#[derive(Iden)]
+pub enum SiteTeamAlias {
+    Table,
+    Alias,
+    SiteTeamId,
+}
+
 #[derive(Iden)]
 pub enum SiteTeamMember {
     Table,
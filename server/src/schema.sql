@@ -16,6 +16,41 @@ CREATE TABLE season(
     driver_changes INTEGER NOT NULL /* boolean */
 );
 
+CREATE TABLE season_schedule(
+    season_id INTEGER NOT NULL,
+    series_id INTEGER NOT NULL,
+    race_week_num INTEGER NOT NULL, /* 0 based */
+    track_id INTEGER NOT NULL,
+    start_date TEXT NOT NULL, /* 2024-06-11 */
+    race_lap_limit INTEGER, /* may be null; races are either lap or time limited */
+    race_time_limit INTEGER, /* may be null; minutes */
+    PRIMARY KEY(season_id, race_week_num)
+);
+
+CREATE TABLE season_driver_standing(
+    season_id INTEGER NOT NULL,
+    car_class_id INTEGER NOT NULL,
+    race_week_num INTEGER NOT NULL, /* standings as of this race week, 0 based */
+    cust_id INTEGER NOT NULL, /* only site team members are stored */
+    division INTEGER NOT NULL, /* 0 -> Division 1, etc */
+    position INTEGER NOT NULL, /* 1 based */
+    points INTEGER NOT NULL,
+    starts INTEGER NOT NULL,
+    wins INTEGER NOT NULL,
+    PRIMARY KEY(season_id, car_class_id, race_week_num, cust_id)
+);
+
+CREATE TABLE season_team_standing(
+    season_id INTEGER NOT NULL,
+    car_class_id INTEGER NOT NULL,
+    race_week_num INTEGER NOT NULL, /* standings as of this race week, 0 based */
+    team_id INTEGER NOT NULL,
+    team_name TEXT NOT NULL,
+    position INTEGER NOT NULL, /* 1 based */
+    points INTEGER NOT NULL,
+    PRIMARY KEY(season_id, car_class_id, race_week_num, team_id)
+);
+
 CREATE TABLE session(
     session_id INTEGER PRIMARY KEY NOT NULL,
     series_name TEXT NOT NULL,
@@ -116,19 +151,120 @@ CREATE TABLE car(
     car_name_abbreviated TEXT NOT NULL
 );
 
+/* site teams are edited through the api, rebuilds carry them over */
 CREATE TABLE site_team(
     site_team_id INTEGER PRIMARY KEY NOT NULL,
-    site_team_name TEXT NOT NULL,
+    site_team_name TEXT NOT NULL UNIQUE,
     discord_hook_url TEXT, /* may be null */
     team_report_discord_hook_url TEXT /* may be null */
 );
 
+CREATE TABLE site_team_alias(
+    alias TEXT PRIMARY KEY NOT NULL, /* other name the site team can be looked up by */
+    site_team_id INTEGER NOT NULL
+);
+
 CREATE TABLE site_team_member(
     site_team_id INTEGER NOT NULL,
-    cust_id INTEGER NOT NULL
+    cust_id INTEGER NOT NULL,
+    PRIMARY KEY(site_team_id, cust_id)
 );
 
 CREATE TABLE site_team_team(
     site_team_id INTEGER NOT NULL,
-    team_id INTEGER NOT NULL
-)
\ No newline at end of file
+    team_id INTEGER NOT NULL,
+    PRIMARY KEY(site_team_id, team_id)
+);
+
+CREATE TABLE metadata(
+    key TEXT PRIMARY KEY NOT NULL,
+    value INTEGER NOT NULL
+);
+
+/* carried over by rebuilds like the site teams */
+CREATE TABLE api_token(
+    token_hash TEXT PRIMARY KEY NOT NULL, /* sha256 of the token, hex */
+    name TEXT NOT NULL UNIQUE, /* who or what the token was issued to */
+    role TEXT NOT NULL, /* viewer, team-manager or admin */
+    site_team_name TEXT, /* the site team a team-manager manages, null for other roles */
+    created_at TEXT NOT NULL /* 2024-06-11 12:00:00 */
+);
+
+/* syncs requested through the api, carried over by rebuilds so job ids stay valid */
+CREATE TABLE sync_job(
+    sync_job_id INTEGER PRIMARY KEY NOT NULL,
+    request TEXT NOT NULL, /* SyncJobRequest as json */
+    requested_by TEXT NOT NULL, /* name of the api token */
+    status TEXT NOT NULL, /* queued, running, done or failed */
+    stage TEXT, /* searching or downloading, null until the sync reports progress */
+    progress_done INTEGER NOT NULL,
+    progress_total INTEGER NOT NULL,
+    error TEXT, /* why a failed job failed */
+    created_at TEXT NOT NULL, /* 2024-06-11 12:00:00 */
+    started_at TEXT,
+    finished_at TEXT
+);
+
+/* what was synced of each driver, carried over by rebuilds */
+CREATE TABLE driver_sync_state(
+    cust_id INTEGER PRIMARY KEY NOT NULL,
+    member_since TEXT, /* 2024-06-11; may be null if the full sync did not need to check it */
+    history_complete INTEGER NOT NULL, /* boolean; every session since member_since was synced */
+    searched_until TEXT /* 2024-06-11T12:00:00Z; sessions started before this were searched and synced, may be null */
+);
+
+/* checkpoints of full driver syncs, so an interrupted one resumes without searching
+   the windows it already searched. Cleared once the sync finished, dropped by rebuilds. */
+CREATE TABLE backfill_window(
+    cust_id INTEGER NOT NULL,
+    start_date TEXT NOT NULL, /* start of the searched 89 day window, 2024-06-11T00:00:00Z */
+    PRIMARY KEY(cust_id, start_date)
+);
+
+CREATE TABLE backfill_subsession(
+    cust_id INTEGER NOT NULL,
+    subsession_id INTEGER NOT NULL, /* found in one of the searched windows, maybe not downloaded yet */
+    PRIMARY KEY(cust_id, subsession_id)
+);
+
+/* iRacing's own numbers for site team members, rebuilt from the member stats cache */
+CREATE TABLE member_profile(
+    cust_id INTEGER PRIMARY KEY NOT NULL,
+    display_name TEXT NOT NULL,
+    member_since TEXT, /* 2011-06-01; may be null */
+    club_name TEXT, /* may be null */
+    synced_at TEXT NOT NULL /* 2024-06-11 12:00:00 */
+);
+
+/* career totals, from /data/stats/member_career */
+CREATE TABLE member_career_stat(
+    cust_id INTEGER NOT NULL,
+    category_id INTEGER NOT NULL, /* 1 -> Oval, 2 -> Road, 3 -> Dirt Oval, 4 -> Dirt Road, 5 -> Sports Car, 6 -> Formula Car */
+    starts INTEGER NOT NULL,
+    wins INTEGER NOT NULL,
+    top5 INTEGER NOT NULL,
+    poles INTEGER NOT NULL,
+    laps INTEGER NOT NULL,
+    laps_led INTEGER NOT NULL,
+    avg_incidents REAL NOT NULL, /* per start */
+    avg_start_position REAL NOT NULL, /* 1 based */
+    avg_finish_position REAL NOT NULL, /* 1 based */
+    PRIMARY KEY(cust_id, category_id)
+);
+
+/* the same per year, from /data/stats/member_yearly */
+CREATE TABLE member_yearly_stat(
+    cust_id INTEGER NOT NULL,
+    year INTEGER NOT NULL,
+    category_id INTEGER NOT NULL,
+    starts INTEGER NOT NULL,
+    wins INTEGER NOT NULL,
+    top5 INTEGER NOT NULL,
+    poles INTEGER NOT NULL,
+    laps INTEGER NOT NULL,
+    laps_led INTEGER NOT NULL,
+    avg_incidents REAL NOT NULL,
+    avg_start_position REAL NOT NULL,
+    avg_finish_position REAL NOT NULL,
+    PRIMARY KEY(cust_id, year, category_id)
+)
@@ -1,91 +1,349 @@
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 use rocket::fs::{FileServer, Options};
-use rocket::State;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Shutdown, State};
+use itertools::Itertools;
 
 use rusqlite::Connection;
+use utoipa::{IntoParams, Modify, OpenApi};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 
 use crate::dirs::get_static_dir;
 use crate::driverid::DriverId;
+use crate::category_type::CategoryType;
+use crate::event_type::EventType;
 use crate::db::{
+    add_site_team_alias_to_db,
+    add_site_team_member_to_db,
+    add_site_team_team_to_db,
     create_r2d2_db_connection_pool,
+    create_site_team_in_db,
+    delete_site_team_from_db,
     query_car_data,
+    query_last_sync_time,
+    query_schema_version,
     query_customer_cust_ids,
     query_customer_names,
     query_driver_sessions,
+    query_member_computed_stats,
+    query_member_official_stats,
+    query_member_profile,
+    query_recent_sync_jobs,
     query_session_result,
     query_site_team_content_usage,
+    query_site_team_definition,
+    query_site_team_definitions,
     query_site_team_driver_pairings,
     query_site_team_members,
+    query_site_team_races,
     query_site_team_report,
+    query_site_team_series,
+    query_site_team_standings_history,
+    query_sync_job,
+    query_season_schedules,
+    query_season_team_standings,
     query_team_results,
-    query_track_data, CustomerName, DbPool, SessionResult, TrackData
+    query_track_data,
+    remove_site_team_alias_from_db,
+    remove_site_team_member_from_db,
+    remove_site_team_team_from_db,
+    replace_site_team_in_db, SCHEMA_VERSION, CustomerName, DbPool, DriverSession, DriverSessionCursor, DriverSessionFilter, DriverSessionPage, DriverContentUsage, ScheduleWeek, MemberStatsData, SessionResult, SiteTeamContentUsage, SiteTeamDefinition, SiteTeamError, SiteTeamMemberDefinition, SiteTeamSeries, SiteTeamTeamDefinition, SiteTeamsFile, SyncJob, SyncJobRequest, TrackData
 };
-use serde_json::{Value, json};
+use crate::api_types::{
+    CalendarResponse,
+    CalendarSeries,
+    CalendarWeek,
+    CarEntry,
+    ContentUsageEntry,
+    ContentUsageResponse,
+    CustomerEntry,
+    DriverInfoResponse,
+    DriverPairingEntry,
+    DriverSessionEntry,
+    DriverStandingsHistory,
+    MemberStatsEntry,
+    MemberStatsResponse,
+    MemberStatsValues,
+    SessionResultEntry,
+    SiteTeamReportEntry,
+    SiteTeamReportResponse,
+    StandingWeekEntry,
+    StandingsHistoryResponse,
+    TeamResultEntry,
+    TeamResultsResponse,
+    TrackCarDataResponse,
+    TrackDataResponse,
+    TrackEntry,
+};
+use crate::xlsx::{write_workbook, Cell, Sheet};
+use crate::icalendar::{render_calendar, CalendarEvent, EventTime};
 use crate::iracing_client::IRacingClient;
-
-#[get("/api/v1/driver-info?<driver_name>&<cust_id>")]
-async fn api_v1_driver_info(
+use crate::report_format::{negotiate_format, AcceptedFormat, Report, ReportFormat};
+use crate::etag::{Conditional, DataETag};
+use crate::auth::{AdminUser, ApiUser, Role, TeamManagerUser};
+use crate::metrics::{Gauge, Metrics, RequestMetrics};
+use crate::server_logger::get_log_file_path;
+use crate::sync_jobs::{SyncJobEvent, SyncJobWorker};
+
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DriverInfoParams {
     driver_name: Option<String>,
     cust_id: Option<i64>,
-    db_pool: &State<DbPool>) -> Option<Value>
+    /// Only sessions started at or after this (YYYY-MM-DD or YYYY-MM-DD HH:MM:SS)
+    since: Option<String>,
+    /// Only sessions started before this (YYYY-MM-DD or YYYY-MM-DD HH:MM:SS)
+    until: Option<String>,
+    /// License category id, as in license_category of the sessions
+    category: Option<i32>,
+    /// Event type id, as in event_type of the sessions
+    event_type: Option<i32>,
+    official: Option<bool>,
+    car_id: Option<i64>,
+    track_id: Option<i64>,
+    /// "asc" (default) or "desc" by start time
+    order: Option<String>,
+    /// next_cursor of the previous page
+    cursor: Option<String>,
+    /// Page size, every session is returned if not given
+    limit: Option<u64>,
+    /// json, csv or ndjson, overrides the Accept header. next_cursor is only part of json.
+    format: Option<String>,
+}
+
+fn encode_driver_session_cursor(session: &DriverSession) -> String {
+    let cursor = format!("{}|{}|{}", session.start_time, session.subsession_id, session.simsession_number);
+    return URL_SAFE_NO_PAD.encode(cursor);
+}
+
+fn decode_driver_session_cursor(cursor: &str) -> Option<DriverSessionCursor> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let mut parts = decoded.split('|');
+    let cursor = DriverSessionCursor{
+        start_time: parts.next()?.to_owned(),
+        subsession_id: parts.next()?.parse().ok()?,
+        simsession_number: parts.next()?.parse().ok()?,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    return Some(cursor);
+}
+
+// None if any of the params are malformed
+fn parse_driver_session_query(params: &DriverInfoParams) -> Option<(DriverSessionFilter, DriverSessionPage)> {
+    let filter = DriverSessionFilter{
+        since: params.since.clone(),
+        until: params.until.clone(),
+        category: match params.category {
+            Some(category) => Some(CategoryType::from_i32(category).ok()?),
+            None => None,
+        },
+        event_type: match params.event_type {
+            Some(event_type) => Some(EventType::from_i32(event_type).ok()?),
+            None => None,
+        },
+        official: params.official,
+        car_id: params.car_id,
+        track_id: params.track_id,
+    };
+
+    let page = DriverSessionPage{
+        descending: match params.order.as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return None,
+        },
+        after: match &params.cursor {
+            Some(cursor) => Some(decode_driver_session_cursor(cursor)?),
+            None => None,
+        },
+        // one extra row tells whether there is a next page
+        limit: params.limit.map(|limit| limit + 1),
+    };
+
+    return Some((filter, page));
+}
+
+#[utoipa::path(
+    params(DriverInfoParams),
+    responses(
+        (status = 200, content(
+            ("application/json" = DriverInfoResponse),
+            ("text/csv" = String),
+            ("application/x-ndjson" = DriverSessionEntry),
+        )),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Malformed filter, order, cursor or format"),
+        (status = 404, description = "Neither driver_name nor cust_id given, or the driver is unknown"),
+    )
+)]
+#[get("/api/v1/driver-info?<params..>")]
+async fn api_v1_driver_info(
+    params: DriverInfoParams,
+    accepted_format: AcceptedFormat,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Result<Conditional<Report<DriverInfoResponse>>, Status>
 {
-    if let Some(driver_id) = DriverId::from_params(driver_name, cust_id) {
+    let Some((filter, page)) = parse_driver_session_query(&params) else {
+        return Err(Status::BadRequest);
+    };
+    let format = negotiate_format(params.format, &accepted_format, ReportFormat::Json)?;
+    if etag.matches() {
+        return Ok(etag.not_modified());
+    }
+
+    if let Some(driver_id) = DriverId::from_params(params.driver_name, params.cust_id) {
         let con = db_pool.get().unwrap();
-        let raw_data = query_driver_sessions(&con, &driver_id)?;
-
-        let values: Vec<Value> = raw_data.iter().map(|data| json!({
-            "subsession_id": data.subsession_id,
-            "old_irating": data.old_irating,
-            "new_irating": data.new_irating,
-            "old_cpi": data.old_cpi,
-            "new_cpi": data.new_cpi,
-            "incidents": data.incidents,
-            "laps_complete": data.laps_complete,
-            "average_lap": data.average_lap,
-            "finish_position_in_class": data.finish_position_in_class,
-            "car_id": data.car_id,
-            "track_id": data.track_id,
-            "package_id": data.package_id,
-            "license_category": data.license_category.to_db_type(),
-            "start_time": data.start_time,
-            "event_type": data.event_type.to_db_type(),
-            "series_name": data.series_name,
-            "session_name": data.session_name,
-            "simsession_number": data.simsession_number,
-            "simsession_type": data.simsession_type,
-            "official_session": data.official_session,
-            "season_year": data.season_year,
-            "season_quarter": data.season_quarter
-        })).collect();
-
-        return Some(json!({
-            "sessions": values
-        }));
+        let mut raw_data = query_driver_sessions(&con, &driver_id, &filter, &page).ok_or(Status::NotFound)?;
+
+        let mut next_cursor = None;
+        if let Some(limit) = params.limit {
+            if raw_data.len() as u64 > limit {
+                raw_data.truncate(limit as usize);
+                next_cursor = raw_data.last().map(encode_driver_session_cursor);
+            }
+        }
+
+        let sessions = raw_data.into_iter().map(|data| DriverSessionEntry{
+            subsession_id: data.subsession_id,
+            old_irating: data.old_irating,
+            new_irating: data.new_irating,
+            old_cpi: data.old_cpi,
+            new_cpi: data.new_cpi,
+            incidents: data.incidents,
+            laps_complete: data.laps_complete,
+            average_lap: data.average_lap,
+            finish_position_in_class: data.finish_position_in_class,
+            car_id: data.car_id,
+            track_id: data.track_id,
+            package_id: data.package_id,
+            license_category: data.license_category.to_db_type(),
+            start_time: data.start_time,
+            event_type: data.event_type.to_db_type(),
+            series_name: data.series_name,
+            session_name: data.session_name,
+            simsession_number: data.simsession_number,
+            simsession_type: data.simsession_type,
+            official_session: data.official_session,
+            season_year: data.season_year,
+            season_quarter: data.season_quarter
+        }).collect();
+
+        return Ok(etag.with(Report::new(format, DriverInfoResponse{ sessions, next_cursor })));
     } else {
-        return None;
+        return Err(Status::NotFound);
     }
 }
 
-fn track_data_to_json(track: TrackData) -> Value {
-    return json!({
-        "package_id": track.package_id,
-        "track_id": track.track_id,
-        "track_name": track.track_name,
-        "config_name": track.config_name,
-        "track_config_length": track.track_config_length,
-        "corners_per_lap": track.corners_per_lap,
-        "category": track.category.to_db_type(),
-        "grid_stalls": track.grid_stalls,
-        "pit_road_speed_limit": track.pit_road_speed_limit,
-        "number_pitstalls": track.number_pitstalls,
-    });
+fn member_stats_data_to_values(stats: &MemberStatsData) -> MemberStatsValues {
+    return MemberStatsValues{
+        starts: stats.starts,
+        wins: stats.wins,
+        top5: stats.top5,
+        poles: stats.poles,
+        laps: stats.laps,
+        laps_led: stats.laps_led,
+        avg_incidents: stats.avg_incidents,
+        avg_start_position: stats.avg_start_position,
+        avg_finish_position: stats.avg_finish_position,
+    };
 }
 
+// Pairs up the official and computed stats of the same year and category
+fn merge_member_stats(official: &Vec<MemberStatsData>, computed: &Vec<MemberStatsData>) -> Vec<MemberStatsEntry> {
+    let mut entries: BTreeMap<(Option<i32>, i32), MemberStatsEntry> = BTreeMap::new();
+    for (stats, is_official) in official.iter().map(|stats| (stats, true)).chain(computed.iter().map(|stats| (stats, false))) {
+        let entry = entries.entry((stats.year, stats.category_id)).or_insert_with(|| MemberStatsEntry{
+            year: stats.year,
+            category_id: stats.category_id,
+            official: None,
+            computed: None,
+            missing_starts: 0,
+        });
+        if is_official {
+            entry.official = Some(member_stats_data_to_values(stats));
+            entry.missing_starts += stats.starts;
+        } else {
+            entry.computed = Some(member_stats_data_to_values(stats));
+            entry.missing_starts -= stats.starts;
+        }
+    }
+    return entries.into_values().collect();
+}
+
+#[utoipa::path(
+    params(("cust_id" = i64, Query, description = "iRacing customer id")),
+    responses(
+        (status = 200, body = MemberStatsResponse, description = "iRacing's career and yearly stats next to the ones computed from the db"),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 404, description = "The member stats were never synced and the db has no official races of the driver"),
+    )
+)]
+#[get("/api/v1/member-stats?<cust_id>")]
+async fn api_v1_member_stats(
+    cust_id: i64,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Result<Conditional<Json<MemberStatsResponse>>, Status>
+{
+    if etag.matches() {
+        return Ok(etag.not_modified());
+    }
+
+    let con = db_pool.get().unwrap();
+    let profile = query_member_profile(&con, cust_id);
+    let (official_yearly, official_career): (Vec<_>, Vec<_>) = query_member_official_stats(&con, cust_id)
+        .into_iter()
+        .partition(|stats| stats.year.is_some());
+    let computed_career = query_member_computed_stats(&con, cust_id, false);
+    let computed_yearly = query_member_computed_stats(&con, cust_id, true);
+    if profile.is_none() && computed_career.is_empty() {
+        return Err(Status::NotFound);
+    }
+
+    return Ok(etag.with(Json(MemberStatsResponse{
+        cust_id,
+        display_name: profile.as_ref().map(|profile| profile.display_name.clone()),
+        member_since: profile.as_ref().and_then(|profile| profile.member_since.clone()),
+        club_name: profile.as_ref().and_then(|profile| profile.club_name.clone()),
+        synced_at: profile.as_ref().map(|profile| profile.synced_at.clone()),
+        career: merge_member_stats(&official_career, &computed_career),
+        yearly: merge_member_stats(&official_yearly, &computed_yearly),
+    })));
+}
+
+fn track_data_to_entry(track: TrackData) -> TrackEntry {
+    return TrackEntry{
+        package_id: track.package_id,
+        track_id: track.track_id,
+        track_name: track.track_name,
+        config_name: track.config_name,
+        track_config_length: track.track_config_length,
+        corners_per_lap: track.corners_per_lap,
+        category: track.category.to_db_type(),
+        grid_stalls: track.grid_stalls,
+        pit_road_speed_limit: track.pit_road_speed_limit,
+        number_pitstalls: track.number_pitstalls,
+    };
+}
+
+#[utoipa::path(responses((status = 200, body = TrackCarDataResponse), (status = 304, description = "Unchanged since the ETag given in If-None-Match")))]
 #[get("/api/v1/track-car-data")]
-async fn api_v1_track_car_data(db_pool: &State<DbPool>) -> Value {
-    // TODO caching
+async fn api_v1_track_car_data(etag: DataETag, db_pool: &State<DbPool>) -> Conditional<Json<TrackCarDataResponse>> {
+    if etag.matches() {
+        return etag.not_modified();
+    }
+
     let con = db_pool.get().unwrap();
 
     let track_data = query_track_data(&con);
@@ -93,39 +351,38 @@ async fn api_v1_track_car_data(db_pool: &State<DbPool>) -> Value {
 
     let mut tracks = Vec::new();
     for track in track_data {
-        tracks.push(track_data_to_json(track));
+        tracks.push(track_data_to_entry(track));
     }
 
     let mut cars = Vec::new();
     for car in car_data {
-        cars.push(json!({
-            "car_id": car.car_id,
-            "car_name": car.car_name,
-            "car_name_abbreviated": car.car_name_abbreviated
-        }));
+        cars.push(CarEntry{
+            car_id: car.car_id,
+            car_name: car.car_name,
+            car_name_abbreviated: car.car_name_abbreviated
+        });
     }
 
-    return json!({
-        "tracks": tracks,
-        "cars": cars
-    });
+    return etag.with(Json(TrackCarDataResponse{ tracks, cars }));
 }
 
+#[utoipa::path(responses((status = 200, body = TrackDataResponse), (status = 304, description = "Unchanged since the ETag given in If-None-Match")))]
 #[get("/api/v1/track-data")]
-async fn api_v1_track_data(db_pool: &State<DbPool>) -> Value {
-    // TODO caching
+async fn api_v1_track_data(etag: DataETag, db_pool: &State<DbPool>) -> Conditional<Json<TrackDataResponse>> {
+    if etag.matches() {
+        return etag.not_modified();
+    }
+
     let con = db_pool.get().unwrap();
 
     let track_data = query_track_data(&con);
 
     let mut tracks = Vec::new();
     for track in track_data {
-        tracks.push(track_data_to_json(track));
+        tracks.push(track_data_to_entry(track));
     }
 
-    return json!({
-        "tracks": tracks,
-    });
+    return etag.with(Json(TrackDataResponse{ tracks }));
 }
 
 fn parse_team_customer_infos(con: &Connection, team: &String) -> Vec<CustomerName> {
@@ -153,11 +410,28 @@ fn parse_drivers_customer_infos(drivers: &String) -> Option<Vec<CustomerName>> {
     return Some(infos);
 }
 
+fn customer_names_to_entries(names: Vec<CustomerName>) -> Vec<CustomerEntry> {
+    return names.into_iter().map(|name| CustomerEntry{
+        name: name.name,
+        cust_id: name.cust_id
+    }).collect();
+}
+
+#[utoipa::path(
+    params(
+        ("team" = Option<String>, Query, description = "Site team name"),
+        ("drivers" = Option<String>, Query, description = "';' separated driver names, cust_ids prefixed with '$'"),
+    ),
+    responses(
+        (status = 200, body = Vec<CustomerEntry>),
+        (status = 404, description = "Neither team nor drivers given, or drivers is malformed"),
+    )
+)]
 #[get("/api/v1/customers?<team>&<drivers>")]
 async fn api_v1_customers(
     team: Option<String>,
     drivers: Option<String>,
-    db_pool: &State<DbPool>) -> Option<Value>
+    db_pool: &State<DbPool>) -> Option<Json<Vec<CustomerEntry>>>
 {
     let con = db_pool.get().unwrap();
 
@@ -187,14 +461,7 @@ async fn api_v1_customers(
     result.append(&mut query_customer_names(&con, cust_ids));
     result.append(&mut query_customer_cust_ids(&con, names));
 
-    let json_arr = result.iter().map(|name| {
-        return json!({
-            "name": name.name,
-            "cust_id": name.cust_id
-        });
-    }).collect();
-
-    return Some(Value::Array(json_arr));
+    return Some(Json(customer_names_to_entries(result)));
 }
 
 fn semi_colon_string_to_i64s(ids: &String) -> Vec<i64> {
@@ -208,102 +475,102 @@ fn semi_colon_string_to_i64s(ids: &String) -> Vec<i64> {
     return id_nums;
 }
 
+#[utoipa::path(
+    params(("cust_ids" = String, Query, description = "';' separated cust_ids")),
+    responses((status = 200, body = Vec<CustomerEntry>), (status = 304, description = "Unchanged since the ETag given in If-None-Match"))
+)]
 #[get("/api/v1/customer-names?<cust_ids>")]
 async fn api_v1_customer_names(
     cust_ids: String,
-    db_pool: &State<DbPool>) -> Value
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Conditional<Json<Vec<CustomerEntry>>>
 {
+    if etag.matches() {
+        return etag.not_modified();
+    }
+
     let cust_id_nums = semi_colon_string_to_i64s(&cust_ids);
 
     let con = db_pool.get().unwrap();
     let names = query_customer_names(&con, cust_id_nums);
 
-    let result = names.iter().map(|name| {
-        return json!({
-            "name": name.name,
-            "cust_id": name.cust_id
-        });
+    return etag.with(Json(customer_names_to_entries(names)));
+}
+
+fn query_team_result_entries(con: &Connection, team_ids: &String) -> TeamResultsResponse {
+    let team_ids = semi_colon_string_to_i64s(team_ids);
+
+    let raw_data = query_team_results(con, team_ids);
+
+    let results = raw_data.into_iter().map(|data| TeamResultEntry{
+        subsession_id: data.subsession_id,
+        cust_id: data.cust_id,
+        team_id: data.team_id,
+        driver_name: data.driver_name,
+        track_id: data.track_id,
+        package_id: data.package_id,
+        car_id: data.car_id,
+        laps_complete: data.laps_complete,
+        finish_position_in_class: data.finish_position_in_class,
+        incidents: data.incidents,
+        start_time: data.start_time,
     }).collect();
 
-    return Value::Array(result);
+    return TeamResultsResponse{ results };
 }
 
+// Kept for existing links, same as team-results?format=csv
+#[utoipa::path(
+    params(("team_ids" = String, Query, description = "';' separated team ids")),
+    responses(
+        (status = 200, content_type = "text/csv", body = String, description = "CSV with a header row, one row per driver result"),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+    )
+)]
 #[get("/api/v1/team-results-csv?<team_ids>")]
 async fn api_v1_team_results_csv(
     team_ids: String,
-    db_pool: &State<DbPool>) -> String
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Conditional<Report<TeamResultsResponse>>
 {
-    let team_ids = semi_colon_string_to_i64s(&team_ids);
+    if etag.matches() {
+        return etag.not_modified();
+    }
 
     let con = db_pool.get().unwrap();
-
-    let raw_data = query_team_results(&con, team_ids);
-
-    let mut writer = csv::Writer::from_writer(Vec::new());
-
-    // header
-    writer.write_record(&[
-        "subsession_id",
-        "cust_id",
-        "team_id",
-        "driver_name",
-        "track_id",
-        "package_id",
-        "car_id",
-        "laps_complete",
-        "finish_position_in_class",
-        "incidents",
-        "start_time"
-    ]).unwrap();
-
-    // values
-    raw_data.iter().for_each(|data| {
-        writer.write_record(&[
-            data.subsession_id.to_string(),
-            data.cust_id.to_string(),
-            data.team_id.to_string(),
-            data.driver_name.to_string(),
-            data.track_id.to_string(),
-            data.package_id.to_string(),
-            data.car_id.to_string(),
-            data.laps_complete.to_string(),
-            data.finish_position_in_class.to_string(),
-            data.incidents.to_string(),
-            data.start_time.to_string()
-        ]).unwrap();
-    });
-
-    return String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    return etag.with(Report::new(ReportFormat::Csv, query_team_result_entries(&con, &team_ids)));
 }
 
-#[get("/api/v1/team-results?<team_ids>")]
+#[utoipa::path(
+    params(
+        ("team_ids" = String, Query, description = "';' separated team ids"),
+        ("format" = Option<String>, Query, description = "json, csv or ndjson, overrides the Accept header"),
+    ),
+    responses(
+        (status = 200, content(
+            ("application/json" = TeamResultsResponse),
+            ("text/csv" = String),
+            ("application/x-ndjson" = TeamResultEntry),
+        )),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Unknown format"),
+    )
+)]
+#[get("/api/v1/team-results?<team_ids>&<format>")]
 async fn api_v1_team_results(
     team_ids: String,
-    db_pool: &State<DbPool>) -> Value
+    format: Option<String>,
+    accepted_format: AcceptedFormat,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Result<Conditional<Report<TeamResultsResponse>>, Status>
 {
-    let team_ids = semi_colon_string_to_i64s(&team_ids);
+    let format = negotiate_format(format, &accepted_format, ReportFormat::Json)?;
+    if etag.matches() {
+        return Ok(etag.not_modified());
+    }
 
     let con = db_pool.get().unwrap();
-
-    let raw_data = query_team_results(&con, team_ids);
-
-    let values: Vec<Value> = raw_data.iter().map(|data| json!({
-        "subsession_id": data.subsession_id,
-        "cust_id": data.cust_id,
-        "team_id": data.team_id,
-        "driver_name": data.driver_name,
-        "track_id": data.track_id,
-        "package_id": data.package_id,
-        "car_id": data.car_id,
-        "laps_complete": data.laps_complete,
-        "finish_position_in_class": data.finish_position_in_class,
-        "incidents": data.incidents,
-        "start_time": data.start_time,
-    })).collect();
-
-    return json!({
-        "results": values
-    });
+    return Ok(etag.with(Report::new(format, query_team_result_entries(&con, &team_ids))));
 }
 
 fn position_str(result: &SessionResult) -> String {
@@ -314,13 +581,38 @@ fn position_str(result: &SessionResult) -> String {
     }
 }
 
-#[get("/api/v1/session-result?<subsession_id>&<subsession_ids>&<team>")]
+// CSV is the default here and has no header row, the archive helper page pastes it as is
+#[utoipa::path(
+    params(
+        ("subsession_ids" = Option<String>, Query, description = "';' separated subsession ids"),
+        ("team" = String, Query, description = "Site team name"),
+        ("format" = Option<String>, Query, description = "json, csv or ndjson, overrides the Accept header"),
+    ),
+    responses(
+        (status = 200, description = "One row per site team member result, CSV has no header row", content(
+            ("text/csv" = String),
+            ("application/json" = Vec<SessionResultEntry>),
+            ("application/x-ndjson" = SessionResultEntry),
+        )),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Unknown format"),
+    )
+)]
+#[get("/api/v1/session-result?<subsession_id>&<subsession_ids>&<team>&<format>")]
 async fn api_v1_session_result(
     subsession_id: Option<i64>,
     subsession_ids: Option<String>,
     team: String,
-    db_pool: &State<DbPool>) -> String
+    format: Option<String>,
+    accepted_format: AcceptedFormat,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Result<Conditional<Report<Vec<SessionResultEntry>>>, Status>
 {
+    let format = negotiate_format(format, &accepted_format, ReportFormat::Csv)?;
+    if etag.matches() {
+        return Ok(etag.not_modified());
+    }
+
     let mut subsession_ids_vec = Vec::new();
     if let Some(subsession_id) = subsession_id {
         subsession_ids_vec.push(subsession_id);
@@ -334,7 +626,7 @@ async fn api_v1_session_result(
 
     let raw_data = query_session_result(&con, subsession_ids_vec, team);
 
-    let mut result = String::new();
+    let mut results = Vec::new();
 
     for driver_result in raw_data {
         let name = if driver_result.session_name.is_empty() {
@@ -343,29 +635,53 @@ async fn api_v1_session_result(
             driver_result.session_name.clone()
         };
 
-        result.push_str(format!("{},{},{},{},{},{},{},{},https://members.iracing.com/membersite/member/EventResult.do?subsessionid={}\n",
+        results.push(SessionResultEntry{
             name,
-            driver_result.start_time.format("%Y.%m.%d"),
-            driver_result.track_id,
-            driver_result.car_id,
-            driver_result.cust_id,
-            driver_result.laps_complete,
-            driver_result.incidents,
-            position_str(&driver_result),
-            driver_result.subsession_id
-        ).as_str());
+            date: driver_result.start_time.format("%Y.%m.%d").to_string(),
+            track_id: driver_result.track_id,
+            car_id: driver_result.car_id,
+            cust_id: driver_result.cust_id,
+            laps_complete: driver_result.laps_complete,
+            incidents: driver_result.incidents,
+            position: position_str(&driver_result),
+            result_url: format!("https://members.iracing.com/membersite/member/EventResult.do?subsessionid={}", driver_result.subsession_id),
+        });
     }
 
-    return result;
+    return Ok(etag.with(Report::new(format, results).without_csv_headers()));
 }
 
-#[get("/api/v1/site-team-report?<site_team>&<start_date>&<end_date>")]
+#[utoipa::path(
+    params(
+        ("start_date" = String, Query, description = "YYYY-MM-DD"),
+        ("end_date" = String, Query, description = "YYYY-MM-DD"),
+        ("format" = Option<String>, Query, description = "json, csv or ndjson, overrides the Accept header"),
+    ),
+    responses(
+        (status = 200, content(
+            ("application/json" = SiteTeamReportResponse),
+            ("text/csv" = String),
+            ("application/x-ndjson" = SiteTeamReportEntry),
+        )),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Unknown format"),
+    )
+)]
+#[get("/api/v1/site-team-report?<site_team>&<start_date>&<end_date>&<format>")]
 async fn api_v1_site_team_report(
     site_team: String,
     start_date: String,
     end_date: String,
-    db_pool: &State<DbPool>) -> Value
+    format: Option<String>,
+    accepted_format: AcceptedFormat,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Result<Conditional<Report<SiteTeamReportResponse>>, Status>
 {
+    let format = negotiate_format(format, &accepted_format, ReportFormat::Json)?;
+    if etag.matches() {
+        return Ok(etag.not_modified());
+    }
+
     let con = db_pool.get().unwrap();
 
     let raw_data = query_site_team_report(
@@ -375,94 +691,1065 @@ async fn api_v1_site_team_report(
         end_date
     );
 
-    let values: Vec<Value> = raw_data.iter().map(|data| json!({
-        "display_name": data.display_name,
-        "laps_complete": data.laps_complete,
-        "incidents": data.incidents,
-        "time_on_track": data.time_on_track,
-        "distance_driven": data.distance_driven,
-        "corners": data.corners,
-        "first_irating": data.first_irating,
-        "last_irating": data.last_irating,
-    })).collect();
-
-    return json!({
-        "results": values
-    });
+    let results = raw_data.into_iter().map(|data| SiteTeamReportEntry{
+        display_name: data.display_name,
+        laps_complete: data.laps_complete,
+        incidents: data.incidents,
+        time_on_track: data.time_on_track,
+        distance_driven: data.distance_driven,
+        corners: data.corners,
+        first_irating: data.first_irating,
+        last_irating: data.last_irating,
+    }).collect();
+
+    return Ok(etag.with(Report::new(format, SiteTeamReportResponse{ results })));
 }
 
-#[get("/api/v1/site-team-pairings?<site_team>")]
+#[utoipa::path(
+    params(("format" = Option<String>, Query, description = "json, csv or ndjson, overrides the Accept header")),
+    responses(
+        (status = 200, content(
+            ("application/json" = Vec<DriverPairingEntry>),
+            ("text/csv" = String),
+            ("application/x-ndjson" = DriverPairingEntry),
+        )),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Unknown format"),
+    )
+)]
+#[get("/api/v1/site-team-pairings?<site_team>&<format>")]
 async fn api_v1_site_team_pairings(
     site_team: String,
-    db_pool: &State<DbPool>) -> Value
+    format: Option<String>,
+    accepted_format: AcceptedFormat,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Result<Conditional<Report<Vec<DriverPairingEntry>>>, Status>
 {
+    let format = negotiate_format(format, &accepted_format, ReportFormat::Json)?;
+    if etag.matches() {
+        return Ok(etag.not_modified());
+    }
+
     let con = db_pool.get().unwrap();
 
     let raw_data = query_site_team_driver_pairings(&con, site_team);
 
-    let values: Vec<Value> = raw_data.iter().map(|data| json!({
-        "driver1": data.driver1,
-        "driver2": data.driver2,
-        "total_time": data.total_time,
-    })).collect();
+    let pairings = raw_data.into_iter().map(|data| DriverPairingEntry{
+        driver1: data.driver1,
+        driver2: data.driver2,
+        total_time: data.total_time,
+    }).collect();
 
-    return Value::Array(values);
+    return Ok(etag.with(Report::new(format, pairings)));
 }
 
+#[utoipa::path(
+    responses((status = 200, body = Vec<i64>, description = "Points of the team after each race week, empty if the season's standings were not synced"))
+)]
 #[get("/api/v1/season-team-standings?<season_id>&<car_class_id>&<team_id>")]
 async fn api_v1_season_team_standings(
     season_id: i64,
     car_class_id: i64,
     mut team_id: i64,
-    iracing_client: &State<IRacingClient>) -> Value
+    db_pool: &State<DbPool>) -> Json<Vec<i64>>
 {
     team_id = team_id.abs();
 
-    let mut weekly_standings = Vec::new();
-    let mut week_num = 0;
-    loop {
-        let standings = iracing_client.get_season_team_standings(season_id, car_class_id, Some(week_num)).await;
-        if let serde_json::Value::Array(standings_arr) = standings {
-            if standings_arr.len() == 0 {
-                break;
-            } else {
-                weekly_standings.push(standings_arr);
-            }
-        } else {
-            break;
+    let stored_standings = {
+        let con = db_pool.get().unwrap();
+        query_season_team_standings(&con, season_id, car_class_id)
+    };
+
+    let week_count = stored_standings.iter().map(|standing| standing.race_week_num + 1).max().unwrap_or(0);
+    let mut points_per_week = vec![0; week_count as usize];
+    for standing in stored_standings {
+        if standing.team_id.abs() == team_id {
+            points_per_week[standing.race_week_num as usize] = standing.points;
         }
-        week_num += 1;
     }
+    return Json(points_per_week);
+}
 
-    let mut points_per_week = Vec::new();
-    for standings in weekly_standings {
-        let mut points = 0;
-        for result in standings {
-            if result["team_id"].as_i64().unwrap().abs() == team_id {
-                // TODO what are raw_points?
-                points = result["points"].as_i64().unwrap();
-                break;
+#[utoipa::path(
+    responses((status = 200, body = StandingsHistoryResponse), (status = 304, description = "Unchanged since the ETag given in If-None-Match"))
+)]
+#[get("/api/v1/site-team-standings-history?<site_team>&<season_id>&<car_class_id>")]
+async fn api_v1_site_team_standings_history(
+    site_team: String,
+    season_id: i64,
+    car_class_id: i64,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Conditional<Json<StandingsHistoryResponse>>
+{
+    if etag.matches() {
+        return etag.not_modified();
+    }
+
+    let con = db_pool.get().unwrap();
+    let history = query_site_team_standings_history(&con, &site_team, season_id, car_class_id);
+
+    // rows are ordered by cust_id, then race week
+    let mut drivers: Vec<DriverStandingsHistory> = Vec::new();
+    for week in history {
+        if drivers.last().map(|driver| driver.cust_id) != Some(week.cust_id) {
+            drivers.push(DriverStandingsHistory{
+                cust_id: week.cust_id,
+                name: week.display_name,
+                weeks: Vec::new(),
+            });
+        }
+        drivers.last_mut().unwrap().weeks.push(StandingWeekEntry{
+            race_week_num: week.race_week_num,
+            division: week.division,
+            position: week.position,
+            points: week.points,
+            starts: week.starts,
+            wins: week.wins,
+        });
+    }
+
+    return etag.with(Json(StandingsHistoryResponse{
+        season_id,
+        car_class_id,
+        drivers,
+    }));
+}
+
+fn content_usage_entries(usage: &SiteTeamContentUsage) -> Vec<ContentUsageEntry> {
+    let mut entries = Vec::new();
+    for (driver_name, driver_usage) in usage.driver_map.iter().sorted_by_key(|(driver_name, _)| *driver_name) {
+        for (content_kind, content_map) in [("track", &driver_usage.track_map), ("car", &driver_usage.car_map)] {
+            for (content_name, time) in content_map.iter().sorted_by_key(|(content_name, time)| (-**time, *content_name)) {
+                entries.push(ContentUsageEntry{
+                    driver_name: driver_name.clone(),
+                    content_kind: content_kind.to_owned(),
+                    content_name: content_name.clone(),
+                    time: *time,
+                });
             }
         }
-        points_per_week.push(points); 
     }
-    return serde_json::to_value(points_per_week).unwrap();
+    return entries;
 }
 
-#[get("/api/v1/site-team-content-usage?<site_team>")]
+#[utoipa::path(
+    params(("format" = Option<String>, Query, description = "json, csv or ndjson, overrides the Accept header")),
+    responses(
+        (status = 200, content(
+            ("application/json" = SiteTeamContentUsage),
+            ("text/csv" = String),
+            ("application/x-ndjson" = ContentUsageEntry),
+        )),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Unknown format"),
+    )
+)]
+#[get("/api/v1/site-team-content-usage?<site_team>&<format>")]
 async fn api_v1_site_team_content_usage(
     site_team: String,
-    db_pool: &State<DbPool>) -> serde_json::Value
+    format: Option<String>,
+    accepted_format: AcceptedFormat,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Result<Conditional<Report<ContentUsageResponse>>, Status>
+{
+    let format = negotiate_format(format, &accepted_format, ReportFormat::Json)?;
+    if etag.matches() {
+        return Ok(etag.not_modified());
+    }
+
+    let con = db_pool.get().unwrap();
+    let usage = query_site_team_content_usage(&con, site_team);
+    let entries = content_usage_entries(&usage);
+
+    return Ok(etag.with(Report::new(format, ContentUsageResponse{ usage, entries })));
+}
+
+// on track times are in 1/10000 seconds
+fn to_hours(time: i64) -> f64 {
+    return time as f64 / 10000.0 / 3600.0;
+}
+
+fn content_usage_rows<F>(usage: &SiteTeamContentUsage, content_map: F) -> Vec<Vec<Cell>>
+    where F: Fn(&DriverContentUsage) -> &HashMap<String, i64>
+{
+    let mut rows = Vec::new();
+    for (driver_name, driver_usage) in usage.driver_map.iter().sorted_by_key(|(driver_name, _)| *driver_name) {
+        for (content_name, time) in content_map(driver_usage).iter().sorted_by_key(|(_, time)| -**time) {
+            rows.push(vec![
+                Cell::Text(driver_name.clone()),
+                Cell::Text(content_name.clone()),
+                Cell::Number(to_hours(*time)),
+            ]);
+        }
+    }
+    return rows;
+}
+
+fn build_site_team_workbook(con: &Connection, site_team: &String, start_date: String, end_date: String) -> Vec<Sheet> {
+    let report = query_site_team_report(con, site_team.clone(), start_date, end_date);
+    let content_usage = query_site_team_content_usage(con, site_team.clone());
+    let pairings = query_site_team_driver_pairings(con, site_team.clone());
+
+    let driver_totals = Sheet{
+        name: "Driver totals".to_owned(),
+        header: vec!["Driver", "Laps", "Incidents", "Time on track (h)", "Distance", "Corners"],
+        rows: report.iter().map(|driver| vec![
+            Cell::Text(driver.display_name.clone()),
+            Cell::Number(driver.laps_complete as f64),
+            Cell::Number(driver.incidents as f64),
+            Cell::Number(to_hours(driver.time_on_track)),
+            Cell::Number(driver.distance_driven as f64),
+            Cell::Number(driver.corners as f64),
+        ]).collect(),
+    };
+
+    let irating_change = Sheet{
+        name: "iRating change".to_owned(),
+        header: vec!["Driver", "First road iRating", "Last road iRating", "Change"],
+        rows: report.iter().map(|driver| vec![
+            Cell::Text(driver.display_name.clone()),
+            Cell::Number(driver.first_irating as f64),
+            Cell::Number(driver.last_irating as f64),
+            Cell::Number((driver.last_irating - driver.first_irating) as f64),
+        ]).collect(),
+    };
+
+    let car_usage = Sheet{
+        name: "Car usage".to_owned(),
+        header: vec!["Driver", "Car", "Time on track (h)"],
+        rows: content_usage_rows(&content_usage, |usage| &usage.car_map),
+    };
+
+    let track_usage = Sheet{
+        name: "Track usage".to_owned(),
+        header: vec!["Driver", "Track", "Time on track (h)"],
+        rows: content_usage_rows(&content_usage, |usage| &usage.track_map),
+    };
+
+    let driver_pairings = Sheet{
+        name: "Pairings".to_owned(),
+        header: vec!["Driver 1", "Driver 2", "Time together (h)"],
+        rows: pairings.iter().map(|pairing| vec![
+            Cell::Text(pairing.driver1.clone()),
+            Cell::Text(pairing.driver2.clone()),
+            Cell::Number(to_hours(pairing.total_time)),
+        ]).collect(),
+    };
+
+    return vec![driver_totals, irating_change, car_usage, track_usage, driver_pairings];
+}
+
+#[derive(Responder)]
+struct XlsxFile {
+    data: Vec<u8>,
+    content_type: ContentType,
+    content_disposition: Header<'static>,
+}
+
+// Driver totals and iRating change cover start_date..end_date, car, track usage and pairings all time
+#[utoipa::path(
+    params(
+        ("start_date" = String, Query, description = "YYYY-MM-DD"),
+        ("end_date" = String, Query, description = "YYYY-MM-DD"),
+    ),
+    responses(
+        (status = 200, content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", body = String,
+            description = "Workbook with driver totals, iRating change, car usage, track usage and pairings sheets"),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+    )
+)]
+#[get("/api/v1/site-team-report.xlsx?<site_team>&<start_date>&<end_date>")]
+async fn api_v1_site_team_report_xlsx(
+    site_team: String,
+    start_date: String,
+    end_date: String,
+    etag: DataETag,
+    db_pool: &State<DbPool>) -> Conditional<XlsxFile>
+{
+    if etag.matches() {
+        return etag.not_modified();
+    }
+
+    let con = db_pool.get().unwrap();
+    let sheets = build_site_team_workbook(&con, &site_team, start_date.clone(), end_date.clone());
+
+    // site team names end up in a header, keep them to a safe set of characters
+    let file_name: String = format!("{site_team}-{start_date}-{end_date}.xlsx").chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+
+    return etag.with(XlsxFile{
+        data: write_workbook(&sheets),
+        content_type: ContentType::new("application", "vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        content_disposition: Header::new("Content-Disposition", format!("attachment; filename=\"{file_name}\"")),
+    });
+}
+
+const DEFAULT_CALENDAR_DAYS: i64 = 90;
+
+struct SiteTeamCalendar {
+    series: Vec<SiteTeamSeries>,
+    // current and upcoming race weeks of the above series
+    weeks: Vec<ScheduleWeek>,
+}
+
+fn query_site_team_calendar(con: &Connection, site_team: &String, days: i64) -> SiteTeamCalendar {
+    let today = chrono::Utc::now().date_naive();
+    let since = today - chrono::Duration::days(days);
+
+    let series = query_site_team_series(con, site_team, since.format("%Y-%m-%d").to_string());
+    let series_ids = series.iter().map(|s| s.series_id).collect();
+
+    // race weeks are 7 days long, this keeps the currently running one
+    let current_week_start = today - chrono::Duration::days(6);
+    let weeks = query_season_schedules(con, series_ids, current_week_start.format("%Y-%m-%d").to_string());
+
+    return SiteTeamCalendar{ series, weeks };
+}
+
+fn schedule_week_to_entry(week: &ScheduleWeek) -> CalendarWeek {
+    return CalendarWeek{
+        season_id: week.season_id,
+        series_id: week.series_id,
+        race_week_num: week.race_week_num,
+        track_id: week.track_id,
+        track_name: week.track_name.clone(),
+        config_name: week.config_name.clone(),
+        start_date: week.start_date.format("%Y-%m-%d").to_string(),
+        race_lap_limit: week.race_lap_limit,
+        race_time_limit: week.race_time_limit,
+    };
+}
+
+#[utoipa::path(
+    params(
+        ("days" = Option<i64>, Query, description = "Look back this many days for raced series, 90 by default"),
+    ),
+    responses((status = 200, body = CalendarResponse))
+)]
+#[get("/api/v1/calendar?<site_team>&<days>")]
+async fn api_v1_calendar(
+    site_team: String,
+    days: Option<i64>,
+    db_pool: &State<DbPool>) -> Json<CalendarResponse>
+{
+    let con = db_pool.get().unwrap();
+    let calendar = query_site_team_calendar(&con, &site_team, days.unwrap_or(DEFAULT_CALENDAR_DAYS));
+    let today = chrono::Utc::now().date_naive();
+
+    let series = calendar.series.iter().map(|series| {
+        let weeks: Vec<&ScheduleWeek> = calendar.weeks.iter().filter(|week| week.series_id == series.series_id).collect();
+        let current_week = weeks.iter().find(|week| week.start_date <= today);
+        let next_week = weeks.iter().find(|week| week.start_date > today);
+
+        return CalendarSeries{
+            series_id: series.series_id,
+            series_name: series.series_name.clone(),
+            race_count: series.race_count,
+            driver_count: series.driver_count,
+            current_week: current_week.map(|week| schedule_week_to_entry(week)),
+            next_week: next_week.map(|week| schedule_week_to_entry(week)),
+        };
+    }).collect();
+
+    return Json(CalendarResponse{ series });
+}
+
+fn track_str(track_name: &String, config_name: &String) -> String {
+    if config_name.is_empty() {
+        return track_name.clone();
+    }
+    return format!("{track_name} - {config_name}");
+}
+
+#[utoipa::path(
+    params(
+        ("days" = Option<i64>, Query, description = "Look back this many days for raced series, 90 by default"),
+    ),
+    responses((status = 200, content_type = "text/calendar", body = String, description = "iCalendar with an all-day event per race week"))
+)]
+#[get("/api/v1/calendar.ics?<site_team>&<days>")]
+async fn api_v1_calendar_ics(
+    site_team: String,
+    days: Option<i64>,
+    db_pool: &State<DbPool>) -> (ContentType, String)
+{
+    let con = db_pool.get().unwrap();
+    let days = days.unwrap_or(DEFAULT_CALENDAR_DAYS);
+    let calendar = query_site_team_calendar(&con, &site_team, days);
+
+    let mut events = Vec::new();
+    for week in &calendar.weeks {
+        let series = calendar.series.iter().find(|series| series.series_id == week.series_id).unwrap();
+
+        let mut description = format!("Week {}", week.race_week_num + 1);
+        if let Some(laps) = week.race_lap_limit {
+            description.push_str(format!(", {laps} laps").as_str());
+        }
+        if let Some(minutes) = week.race_time_limit {
+            description.push_str(format!(", {minutes} minutes").as_str());
+        }
+        description.push_str(format!("\n{} races by {} drivers of {site_team} in the last {days} days",
+            series.race_count, series.driver_count).as_str());
+
+        events.push(CalendarEvent{
+            uid: format!("{}-{}@iracing-stats", week.season_id, week.race_week_num),
+            summary: format!("{}: {}", series.series_name, track_str(&week.track_name, &week.config_name)),
+            description,
+            start: EventTime::Date(week.start_date),
+            end: EventTime::Date(week.start_date + chrono::Duration::days(7)),
+        });
+    }
+
+    return (ContentType::Calendar, render_calendar(&format!("{site_team} series calendar"), &events));
+}
+
+#[utoipa::path(
+    params(
+        ("days" = Option<i64>, Query, description = "Races of the last this many days, 90 by default"),
+    ),
+    responses((status = 200, content_type = "text/calendar", body = String, description = "iCalendar with an event per race per driver"))
+)]
+#[get("/api/v1/site-team-calendar.ics?<site_team>&<days>")]
+async fn api_v1_site_team_calendar_ics(
+    site_team: String,
+    days: Option<i64>,
+    db_pool: &State<DbPool>) -> (ContentType, String)
+{
+    let con = db_pool.get().unwrap();
+    let since = chrono::Utc::now() - chrono::Duration::days(days.unwrap_or(DEFAULT_CALENDAR_DAYS));
+    let races = query_site_team_races(&con, &site_team, since.format("%Y-%m-%d").to_string());
+
+    let mut events = Vec::new();
+    for race in races {
+        let name = if race.session_name.is_empty() {
+            race.series_name.clone()
+        } else {
+            race.session_name.clone()
+        };
+
+        let position = if race.reason_out == "Running" {
+            format!("P{}/{}", race.finish_position_in_class + 1, race.entries_in_class)
+        } else {
+            format!("DNF ({})", race.reason_out)
+        };
+
+        // average_lap is in 1/10000 seconds
+        let race_duration = chrono::Duration::milliseconds(race.laps_complete * race.average_lap / 10);
+
+        events.push(CalendarEvent{
+            uid: format!("{}-{}@iracing-stats", race.subsession_id, race.cust_id),
+            summary: format!("{}: {} {}", race.driver_name, position, name),
+            description: format!("{}\n{}\n{}\nFinish: {}, {} laps\nhttps://members.iracing.com/membersite/member/EventResult.do?subsessionid={}",
+                name,
+                track_str(&race.track_name, &race.config_name),
+                race.car_name,
+                position,
+                race.laps_complete,
+                race.subsession_id
+            ),
+            start: EventTime::DateTime(race.start_time),
+            end: EventTime::DateTime(race.start_time + race_duration.max(chrono::Duration::minutes(15))),
+        });
+    }
+
+    return (ContentType::Calendar, render_calendar(&format!("{site_team} races"), &events));
+}
+
+// Team managers may only change the site team they manage
+fn check_can_manage_site_team(user: &TeamManagerUser, name: &str) -> Result<(), (Status, String)> {
+    if !user.0.can_manage_site_team(name) {
+        return Err((Status::Forbidden, format!("Not a manager of site team '{name}'")));
+    }
+    return Ok(());
+}
+
+// Anyone with the hook urls can post to the team's channels, only its managers see them
+fn hide_hooks_unless_manager(user: &ApiUser, mut site_team: SiteTeamDefinition) -> SiteTeamDefinition {
+    if !user.can_manage_site_team(&site_team.name) {
+        site_team.discord_hook_url = None;
+        site_team.team_report_discord_hook_url = None;
+    }
+    return site_team;
+}
+
+fn site_team_error_response(error: SiteTeamError) -> (Status, String) {
+    return match error {
+        SiteTeamError::NotFound => (Status::NotFound, "Site team or entry not found".to_owned()),
+        SiteTeamError::NameTaken(name) => (Status::Conflict, format!("'{name}' is already the name or an alias of a site team")),
+    };
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 200, body = Vec<SiteTeamDefinition>, description = "The discord hook urls are left out of teams the token can't manage"),
+        (status = 401, description = "Missing or unknown api token"),
+    )
+)]
+#[get("/api/v1/site-teams")]
+async fn api_v1_site_teams(user: ApiUser, db_pool: &State<DbPool>) -> Json<Vec<SiteTeamDefinition>> {
+    let con = db_pool.get().unwrap();
+    let site_teams = query_site_team_definitions(&con).into_iter()
+        .map(|site_team| hide_hooks_unless_manager(&user, site_team))
+        .collect();
+    return Json(site_teams);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 200, body = SiteTeamDefinition, description = "The discord hook urls are left out unless the token can manage the team"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 404, description = "No site team with this name"),
+    )
+)]
+#[get("/api/v1/site-teams/<name>")]
+async fn api_v1_site_team(name: String, user: ApiUser, db_pool: &State<DbPool>) -> Option<Json<SiteTeamDefinition>> {
+    let con = db_pool.get().unwrap();
+    return query_site_team_definition(&con, &name).map(|site_team| Json(hide_hooks_unless_manager(&user, site_team)));
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    request_body = SiteTeamDefinition,
+    responses(
+        (status = 201, description = "Created"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 409, description = "The name or one of the aliases is already used by a site team"),
+    )
+)]
+#[post("/api/v1/site-teams", data = "<site_team>")]
+async fn api_v1_create_site_team(
+    site_team: Json<SiteTeamDefinition>,
+    admin: AdminUser,
+    db_pool: &State<DbPool>) -> Result<Status, (Status, String)>
+{
+    let mut con = db_pool.get().unwrap();
+    create_site_team_in_db(&mut con, &site_team).map_err(site_team_error_response)?;
+    println!("Site team {} created by {}", site_team.name, admin.0.name);
+    return Ok(Status::Created);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    request_body = SiteTeamDefinition,
+    responses(
+        (status = 204, description = "Replaced, including the name, aliases, members and teams"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name"),
+        (status = 409, description = "The new name or one of the aliases is already used by another site team"),
+    )
+)]
+#[put("/api/v1/site-teams/<name>", data = "<site_team>")]
+async fn api_v1_replace_site_team(
+    name: String,
+    site_team: Json<SiteTeamDefinition>,
+    user: TeamManagerUser,
+    db_pool: &State<DbPool>) -> Result<Status, (Status, String)>
+{
+    check_can_manage_site_team(&user, &name)?;
+    let mut con = db_pool.get().unwrap();
+    replace_site_team_in_db(&mut con, &name, &site_team).map_err(site_team_error_response)?;
+    println!("Site team {name} replaced by {}", user.0.name);
+    return Ok(Status::NoContent);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name"),
+    )
+)]
+#[delete("/api/v1/site-teams/<name>")]
+async fn api_v1_delete_site_team(name: String, admin: AdminUser, db_pool: &State<DbPool>) -> Result<Status, (Status, String)> {
+    let mut con = db_pool.get().unwrap();
+    delete_site_team_from_db(&mut con, &name).map_err(site_team_error_response)?;
+    println!("Site team {name} deleted by {}", admin.0.name);
+    return Ok(Status::NoContent);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 204, description = "Added, or was already an alias of this site team"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name"),
+        (status = 409, description = "The alias is already used by another site team"),
+    )
+)]
+#[put("/api/v1/site-teams/<name>/aliases/<alias>")]
+async fn api_v1_add_site_team_alias(name: String, alias: String, user: TeamManagerUser, db_pool: &State<DbPool>) -> Result<Status, (Status, String)> {
+    check_can_manage_site_team(&user, &name)?;
+    let mut con = db_pool.get().unwrap();
+    add_site_team_alias_to_db(&mut con, &name, &alias).map_err(site_team_error_response)?;
+    return Ok(Status::NoContent);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 204, description = "Removed"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name or no such alias"),
+    )
+)]
+#[delete("/api/v1/site-teams/<name>/aliases/<alias>")]
+async fn api_v1_remove_site_team_alias(name: String, alias: String, user: TeamManagerUser, db_pool: &State<DbPool>) -> Result<Status, (Status, String)> {
+    check_can_manage_site_team(&user, &name)?;
+    let mut con = db_pool.get().unwrap();
+    remove_site_team_alias_from_db(&mut con, &name, &alias).map_err(site_team_error_response)?;
+    return Ok(Status::NoContent);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 204, description = "Added, or was already a member"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name"),
+    )
+)]
+#[put("/api/v1/site-teams/<name>/members/<cust_id>")]
+async fn api_v1_add_site_team_member(name: String, cust_id: i64, user: TeamManagerUser, db_pool: &State<DbPool>) -> Result<Status, (Status, String)> {
+    check_can_manage_site_team(&user, &name)?;
+    let mut con = db_pool.get().unwrap();
+    add_site_team_member_to_db(&mut con, &name, cust_id).map_err(site_team_error_response)?;
+    return Ok(Status::NoContent);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 204, description = "Removed"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name or not a member"),
+    )
+)]
+#[delete("/api/v1/site-teams/<name>/members/<cust_id>")]
+async fn api_v1_remove_site_team_member(name: String, cust_id: i64, user: TeamManagerUser, db_pool: &State<DbPool>) -> Result<Status, (Status, String)> {
+    check_can_manage_site_team(&user, &name)?;
+    let mut con = db_pool.get().unwrap();
+    remove_site_team_member_from_db(&mut con, &name, cust_id).map_err(site_team_error_response)?;
+    return Ok(Status::NoContent);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 204, description = "Added, or was already one of the site team's iRacing teams"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name"),
+    )
+)]
+#[put("/api/v1/site-teams/<name>/teams/<team_id>")]
+async fn api_v1_add_site_team_team(name: String, team_id: i64, user: TeamManagerUser, db_pool: &State<DbPool>) -> Result<Status, (Status, String)> {
+    check_can_manage_site_team(&user, &name)?;
+    let mut con = db_pool.get().unwrap();
+    add_site_team_team_to_db(&mut con, &name, team_id).map_err(site_team_error_response)?;
+    return Ok(Status::NoContent);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 204, description = "Removed"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+        (status = 404, description = "No site team with this name or not one of its iRacing teams"),
+    )
+)]
+#[delete("/api/v1/site-teams/<name>/teams/<team_id>")]
+async fn api_v1_remove_site_team_team(name: String, team_id: i64, user: TeamManagerUser, db_pool: &State<DbPool>) -> Result<Status, (Status, String)> {
+    check_can_manage_site_team(&user, &name)?;
+    let mut con = db_pool.get().unwrap();
+    remove_site_team_team_from_db(&mut con, &name, team_id).map_err(site_team_error_response)?;
+    return Ok(Status::NoContent);
+}
+
+// Same format as static-data/site-teams.json, for backups
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 200, body = SiteTeamsFile),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this"),
+    )
+)]
+#[get("/api/v1/site-teams-export")]
+async fn api_v1_site_teams_export(_admin: AdminUser, db_pool: &State<DbPool>) -> Json<SiteTeamsFile> {
+    let con = db_pool.get().unwrap();
+    return Json(SiteTeamsFile{ site_teams: query_site_team_definitions(&con) });
+}
+
+const RECENT_SYNC_JOB_COUNT: i64 = 50;
+
+// Admins may sync anything, team managers only the drivers of their site team
+fn check_can_request_sync(user: &TeamManagerUser, con: &Connection, request: &SyncJobRequest) -> Result<(), (Status, String)> {
+    if user.0.role == Role::Admin {
+        return Ok(());
+    }
+    let SyncJobRequest::CustIds{ cust_ids, .. } = request else {
+        return Err((Status::Forbidden, "Team managers may only sync drivers".to_owned()));
+    };
+    let site_team_name = user.0.site_team_name.clone().unwrap_or_default();
+    let members: Vec<i64> = match query_site_team_definition(con, &site_team_name) {
+        Some(site_team) => site_team.members.iter().map(|member| member.cust_id).collect(),
+        None => Vec::new(),
+    };
+    if let Some(cust_id) = cust_ids.iter().find(|cust_id| !members.contains(cust_id)) {
+        return Err((Status::Forbidden, format!("{cust_id} is not a member of site team '{site_team_name}'")));
+    }
+    return Ok(());
+}
+
+fn check_sync_job_request(request: &SyncJobRequest) -> Result<(), (Status, String)> {
+    let valid = match request {
+        SyncJobRequest::CustIds{ cust_ids, .. } => !cust_ids.is_empty(),
+        SyncJobRequest::SubsessionIds{ subsession_ids } => !subsession_ids.is_empty(),
+        SyncJobRequest::Season{ quarter, .. } => (1..=4).contains(quarter),
+        SyncJobRequest::CarInfos | SyncJobRequest::TrackInfos => true,
+    };
+    if !valid {
+        return Err((Status::BadRequest, "Nothing to sync, or the quarter is not 1-4".to_owned()));
+    }
+    return Ok(());
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    request_body = SyncJobRequest,
+    responses(
+        (status = 202, body = SyncJob, description = "Queued, poll /api/v1/sync-jobs/{sync_job_id} for progress"),
+        (status = 400, description = "Empty id list or invalid quarter"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 403, description = "The token's role does not allow this sync"),
+    )
+)]
+#[post("/api/v1/sync-jobs", data = "<request>")]
+async fn api_v1_create_sync_job(
+    request: Json<SyncJobRequest>,
+    user: TeamManagerUser,
+    db_pool: &State<DbPool>,
+    sync_job_worker: &State<SyncJobWorker>) -> Result<(Status, Json<SyncJob>), (Status, String)>
 {
+    check_sync_job_request(&request)?;
+    let con = db_pool.get().unwrap();
+    check_can_request_sync(&user, &con, &request)?;
+    let job = sync_job_worker.enqueue(&con, &request, &user.0.name);
+    return Ok((Status::Accepted, Json(job)));
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 200, body = Vec<SyncJob>, description = "The latest 50 jobs, newest first"),
+        (status = 401, description = "Missing or unknown api token"),
+    )
+)]
+#[get("/api/v1/sync-jobs")]
+async fn api_v1_sync_jobs(_user: ApiUser, db_pool: &State<DbPool>) -> Json<Vec<SyncJob>> {
     let con = db_pool.get().unwrap();
-    let data = query_site_team_content_usage(&con, site_team);
+    return Json(query_recent_sync_jobs(&con, RECENT_SYNC_JOB_COUNT));
+}
 
-    return serde_json::to_value(&data).unwrap();
+#[utoipa::path(
+    security(("api_token" = [])),
+    responses(
+        (status = 200, body = SyncJob),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 404, description = "No sync job with this id"),
+    )
+)]
+#[get("/api/v1/sync-jobs/<sync_job_id>")]
+async fn api_v1_sync_job(sync_job_id: i64, _user: ApiUser, db_pool: &State<DbPool>) -> Option<Json<SyncJob>> {
+    let con = db_pool.get().unwrap();
+    return query_sync_job(&con, sync_job_id).map(Json);
+}
+
+#[utoipa::path(
+    security(("api_token" = [])),
+    params(("sync_job_id" = Option<i64>, Query, description = "Only the events of this job. The stream ends once it finished.")),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = SyncJobEvent,
+            description = "Server-Sent Events named like the \"event\" field of their data"),
+        (status = 401, description = "Missing or unknown api token"),
+        (status = 404, description = "No sync job with this id"),
+    )
+)]
+#[get("/api/v1/sync-job-events?<sync_job_id>")]
+async fn api_v1_sync_job_events(
+    sync_job_id: Option<i64>,
+    _user: ApiUser,
+    db_pool: &State<DbPool>,
+    sync_job_worker: &State<SyncJobWorker>,
+    mut shutdown: Shutdown) -> Option<EventStream![]>
+{
+    // subscribe before looking at the job so no event falls in between
+    let mut events = sync_job_worker.subscribe();
+    let finished_event = match sync_job_id {
+        Some(sync_job_id) => finished_job_event(query_sync_job(&db_pool.get().unwrap(), sync_job_id)?),
+        None => None,
+    };
+    let db_pool = db_pool.inner().clone();
+
+    return Some(EventStream! {
+        if let Some(event) = finished_event {
+            yield Event::json(&event).event(event.name());
+            return;
+        }
+        loop {
+            let received = select! {
+                received = events.recv() => received,
+                _ = &mut shutdown => break,
+            };
+            let event = match received {
+                Ok(event) => event,
+                Err(RecvError::Closed) => break,
+                // the job's Finished event may be among the skipped ones
+                Err(RecvError::Lagged(_)) => {
+                    let job = sync_job_id.and_then(|sync_job_id| query_sync_job(&db_pool.get().unwrap(), sync_job_id));
+                    match job.and_then(finished_job_event) {
+                        Some(event) => event,
+                        None => continue,
+                    }
+                },
+            };
+            if sync_job_id.is_some_and(|sync_job_id| sync_job_id != event.sync_job_id()) {
+                continue;
+            }
+            yield Event::json(&event).event(event.name());
+            if sync_job_id.is_some() && matches!(event, SyncJobEvent::Finished{ .. }) {
+                break;
+            }
+        }
+    });
+}
+
+// The event a job that already finished would have ended with
+fn finished_job_event(job: SyncJob) -> Option<SyncJobEvent> {
+    if job.status != "done" && job.status != "failed" {
+        return None;
+    }
+    return Some(SyncJobEvent::Finished{ sync_job_id: job.sync_job_id, status: job.status, error: job.error });
+}
+
+// Operational endpoints for process supervisors and monitoring, not part of /api/v1
+
+#[utoipa::path(responses((status = 200, body = String, description = "The server is up")))]
+#[get("/healthz")]
+async fn healthz() -> &'static str {
+    return "ok";
+}
+
+// Ready once the db can be queried and was built with the schema this binary expects
+#[utoipa::path(
+    responses(
+        (status = 200, body = String, description = "Ready"),
+        (status = 503, body = String, description = "The db is unavailable or has another schema version"),
+    )
+)]
+#[get("/readyz")]
+async fn readyz(db_pool: &State<DbPool>) -> (Status, String) {
+    let con = match db_pool.get_timeout(std::time::Duration::from_secs(2)) {
+        Ok(con) => con,
+        Err(error) => return (Status::ServiceUnavailable, format!("db unavailable: {error}")),
+    };
+    return match query_schema_version(&con) {
+        Ok(version) if version == SCHEMA_VERSION => (Status::Ok, "ok".to_owned()),
+        Ok(version) => (Status::ServiceUnavailable, format!("db schema version is {version}, expected {SCHEMA_VERSION}, rebuild the db")),
+        Err(error) => (Status::ServiceUnavailable, format!("db unavailable: {error}")),
+    };
+}
+
+#[utoipa::path(responses((status = 200, content_type = "text/plain", body = String, description = "Prometheus text format")))]
+#[get("/metrics")]
+async fn metrics(
+    metrics: &State<Metrics>,
+    db_pool: &State<DbPool>,
+    iracing_client: &State<Arc<IRacingClient>>) -> (ContentType, String)
+{
+    let pool_state = db_pool.state();
+    let mut gauges = vec![
+        Gauge{ name: "iracing_stats_db_pool_max_connections", help: "Maximum size of the db connection pool", value: db_pool.max_size() as f64 },
+        Gauge{ name: "iracing_stats_db_pool_connections", help: "Open connections of the db connection pool", value: pool_state.connections as f64 },
+        Gauge{ name: "iracing_stats_db_pool_idle_connections", help: "Idle connections of the db connection pool", value: pool_state.idle_connections as f64 },
+        Gauge{ name: "iracing_stats_iracing_rate_limit_remaining", help: "Remaining iRacing API requests as last reported by the API",
+            value: iracing_client.rate_limit_remaining.load(Ordering::Relaxed) as f64 },
+        Gauge{ name: "iracing_stats_iracing_rate_limit_reset_timestamp_seconds", help: "When the iRacing API rate limit resets",
+            value: iracing_client.rate_limit_reset.load(Ordering::Relaxed) as f64 },
+    ];
+
+    // an exhausted pool should not hold up the scrape
+    if let Ok(con) = db_pool.get_timeout(std::time::Duration::from_secs(1)) {
+        if let Some(last_sync_time) = query_last_sync_time(&con) {
+            gauges.push(Gauge{ name: "iracing_stats_last_sync_timestamp_seconds", help: "When the last sync from the iRacing API finished",
+                value: last_sync_time as f64 });
+        }
+    }
+
+    return (ContentType::new("text", "plain").with_params(("version", "0.0.4")), metrics.render(&gauges));
+}
+
+fn ops_routes() -> Vec<rocket::Route> {
+    return routes![healthz, readyz, metrics];
+}
+
+// Everything mounted next to the site files
+fn mounted_routes() -> Vec<rocket::Route> {
+    return api_routes().into_iter().chain(ops_routes()).collect();
+}
+
+#[utoipa::path(responses((status = 200, content_type = "application/json", body = Object, description = "This document")))]
+#[get("/api/v1/openapi.json")]
+async fn api_v1_openapi() -> Json<utoipa::openapi::OpenApi> {
+    return Json(ApiDoc::openapi());
+}
+
+// Bearer token security scheme referred to by the routes requiring an api token
+struct ApiTokenSecurity;
+
+impl Modify for ApiTokenSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("api_token", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "iracing-stats"),
+    modifiers(&ApiTokenSecurity),
+    paths(
+        api_v1_customers,
+        api_v1_customer_names,
+        api_v1_driver_info,
+        api_v1_member_stats,
+        api_v1_track_data,
+        api_v1_track_car_data,
+        api_v1_team_results,
+        api_v1_team_results_csv,
+        api_v1_session_result,
+        api_v1_site_team_report,
+        api_v1_site_team_pairings,
+        api_v1_season_team_standings,
+        api_v1_site_team_standings_history,
+        api_v1_site_team_content_usage,
+        api_v1_site_team_report_xlsx,
+        api_v1_calendar,
+        api_v1_calendar_ics,
+        api_v1_site_team_calendar_ics,
+        api_v1_site_teams,
+        api_v1_site_team,
+        api_v1_create_site_team,
+        api_v1_replace_site_team,
+        api_v1_delete_site_team,
+        api_v1_add_site_team_alias,
+        api_v1_remove_site_team_alias,
+        api_v1_add_site_team_member,
+        api_v1_remove_site_team_member,
+        api_v1_add_site_team_team,
+        api_v1_remove_site_team_team,
+        api_v1_site_teams_export,
+        api_v1_create_sync_job,
+        api_v1_sync_jobs,
+        api_v1_sync_job,
+        api_v1_sync_job_events,
+        api_v1_openapi,
+        healthz,
+        readyz,
+        metrics,
+    ),
+    components(schemas(
+        DriverSessionEntry,
+        DriverInfoResponse,
+        TrackEntry,
+        CarEntry,
+        TrackDataResponse,
+        TrackCarDataResponse,
+        CustomerEntry,
+        TeamResultEntry,
+        TeamResultsResponse,
+        SiteTeamReportEntry,
+        SiteTeamReportResponse,
+        SessionResultEntry,
+        DriverPairingEntry,
+        StandingWeekEntry,
+        DriverStandingsHistory,
+        StandingsHistoryResponse,
+        DriverContentUsage,
+        SiteTeamContentUsage,
+        ContentUsageEntry,
+        CalendarWeek,
+        CalendarSeries,
+        CalendarResponse,
+        SiteTeamDefinition,
+        SiteTeamMemberDefinition,
+        SiteTeamTeamDefinition,
+        SiteTeamsFile,
+        SyncJobRequest,
+        SyncJob,
+        SyncJobEvent,
+        MemberStatsValues,
+        MemberStatsEntry,
+        MemberStatsResponse,
+    ))
+)]
+struct ApiDoc;
+
+// Every route here has to be listed in ApiDoc too
+fn api_routes() -> Vec<rocket::Route> {
+    return routes![
+        api_v1_customers,
+        api_v1_customer_names,
+        api_v1_driver_info,
+        api_v1_member_stats,
+        api_v1_track_data,
+        api_v1_track_car_data,
+        api_v1_team_results,
+        api_v1_team_results_csv,
+        api_v1_session_result,
+        api_v1_site_team_report,
+        api_v1_site_team_pairings,
+        api_v1_season_team_standings,
+        api_v1_site_team_standings_history,
+        api_v1_site_team_content_usage,
+        api_v1_site_team_report_xlsx,
+        api_v1_calendar,
+        api_v1_calendar_ics,
+        api_v1_site_team_calendar_ics,
+        api_v1_site_teams,
+        api_v1_site_team,
+        api_v1_create_site_team,
+        api_v1_replace_site_team,
+        api_v1_delete_site_team,
+        api_v1_add_site_team_alias,
+        api_v1_remove_site_team_alias,
+        api_v1_add_site_team_member,
+        api_v1_remove_site_team_member,
+        api_v1_add_site_team_team,
+        api_v1_remove_site_team_team,
+        api_v1_site_teams_export,
+        api_v1_create_sync_job,
+        api_v1_sync_jobs,
+        api_v1_sync_job,
+        api_v1_sync_job_events,
+        api_v1_openapi,
+    ];
 }
 
 pub async fn start_rocket_server(enable_https: bool) {
     const SITE_DIR_ENV_VAR: &str = "IRACING_STATS_SITE_DIR";
-    const LOG_FILE_ENV_VAR: &str = "IRACING_STATS_LOG_FILE";
 
     let db_pool = create_r2d2_db_connection_pool();
 
@@ -478,30 +1765,46 @@ pub async fn start_rocket_server(enable_https: bool) {
         Err(_error) => "../site/dist".to_owned()
     };
 
-    let log_file = match env::var(LOG_FILE_ENV_VAR) {
-        Ok(value) => value,
-        Err(_error) => "server.log".to_owned()
-    };
-    let server_logger = crate::server_logger::ServerLogger::new(PathBuf::from(log_file));
+    let server_logger = crate::server_logger::ServerLogger::new(get_log_file_path());
+
+    // One authenticated client is shared by /metrics and the sync jobs. Without
+    // credentials the server still runs, only the syncs fail.
+    let iracing_client = Arc::new(IRacingClient::new());
+    if let Err(error) = iracing_client.ensure_session().await {
+        println!("Not logged in to iRacing: {error}");
+    }
+    let sync_job_worker = SyncJobWorker::start(iracing_client.clone());
 
     let _result = rocket::custom(figment)
         .mount("/", FileServer::new(site_dir, Options::Index))
-        .mount("/", routes![
-            api_v1_customers,
-            api_v1_customer_names,
-            api_v1_driver_info,
-            api_v1_track_data,
-            api_v1_track_car_data,
-            api_v1_team_results,
-            api_v1_team_results_csv,
-            api_v1_session_result,
-            api_v1_site_team_report,
-            api_v1_site_team_pairings,
-            api_v1_season_team_standings,
-            api_v1_site_team_content_usage
-        ])
-        .manage(IRacingClient::new())
+        .mount("/", mounted_routes())
+        .manage(iracing_client)
+        .manage(sync_job_worker)
         .manage(db_pool)
+        .manage(Metrics::default())
         .attach(server_logger)
+        .attach(RequestMetrics)
         .launch().await.unwrap();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_mounted_route_is_described_in_openapi() {
+        let doc = ApiDoc::openapi();
+
+        for route in mounted_routes() {
+            // rocket writes dynamic segments as <name>, OpenAPI as {name}
+            let path = route.uri.path().to_string().replace('<', "{").replace('>', "}");
+
+            let path_item = doc.paths.paths.get(&path)
+                .unwrap_or_else(|| panic!("{path} is not described in the OpenAPI document"));
+
+            let described = path_item.operations.keys()
+                .any(|method| serde_json::to_value(method).unwrap().as_str().unwrap().eq_ignore_ascii_case(route.method.as_str()));
+            assert!(described, "{} {path} is not described in the OpenAPI document", route.method);
+        }
+    }
+}
@@ -1,32 +1,242 @@
-use rocket::{fairing::Fairing, Request};
-use std::fs::OpenOptions;
-use std::path::PathBuf;
-use std::io::prelude::*;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Orbit, Request, Response, Rocket};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{prelude::*, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, NaiveDate, Utc};
+use itertools::Itertools;
+
+const LOG_FILE_ENV_VAR: &str = "IRACING_STATS_LOG_FILE";
+// the log is rotated when it grows past this or on the first request of a new (UTC) day
+const MAX_LOG_FILE_SIZE: u64 = 50 * 1024 * 1024;
+// buffered lines are written out this often, by a task started at liftoff
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn get_log_file_path() -> PathBuf {
+    return match env::var(LOG_FILE_ENV_VAR) {
+        Ok(value) => PathBuf::from(value),
+        Err(_error) => PathBuf::from("server.log"),
+    };
+}
+
+// One line of the log, as JSON
+#[derive(Serialize, Deserialize)]
+struct LogLine {
+    time: String, // RFC 3339
+    method: String,
+    uri: String,
+    status: u16,
+    latency_ms: f64,
+    client: Option<String>,
+}
+
+struct LogWriter {
+    writer: BufWriter<File>,
+    size: u64,
+    day: NaiveDate,
+}
 
 pub struct ServerLogger {
-    log_file_path: PathBuf
+    log_file_path: PathBuf,
+    // shared with the flush task
+    log_writer: Arc<Mutex<Option<LogWriter>>>,
+}
+
+struct RequestStart(Instant);
+
+fn open_log_writer(path: &Path) -> LogWriter {
+    let file = OpenOptions::new().append(true).create(true).open(path).unwrap();
+    let metadata = file.metadata().unwrap();
+    let modified: DateTime<Utc> = metadata.modified().map(DateTime::from).unwrap_or(Utc::now());
+    return LogWriter {
+        writer: BufWriter::new(file),
+        size: metadata.len(),
+        day: modified.date_naive(),
+    };
+}
+
+// server.log -> server.log.2024-06-11, server.log.2024-06-11.1, ...
+fn rotated_log_file_path(path: &Path, day: NaiveDate) -> PathBuf {
+    let base = format!("{}.{}", path.display(), day.format("%Y-%m-%d"));
+    let mut rotated = PathBuf::from(&base);
+    let mut i = 1;
+    while rotated.exists() {
+        rotated = PathBuf::from(format!("{base}.{i}"));
+        i += 1;
+    }
+    return rotated;
 }
 
 impl ServerLogger {
     pub fn new(log_file_path: PathBuf) -> Self {
-        return Self { log_file_path };
+        return Self { log_file_path, log_writer: Arc::new(Mutex::new(None)) };
+    }
+
+    fn flush(log_writer: &Mutex<Option<LogWriter>>) {
+        if let Some(log_writer) = log_writer.lock().unwrap().as_mut() {
+            log_writer.writer.flush().ok(); // ignore error
+        }
+    }
+
+    fn write_line(&self, now: DateTime<Utc>, line: &LogLine) {
+        let mut json = serde_json::to_string(line).unwrap();
+        json.push('\n');
+
+        let mut log_writer = self.log_writer.lock().unwrap();
+        let today = now.date_naive();
+
+        let current = log_writer.get_or_insert_with(|| open_log_writer(&self.log_file_path));
+        // an empty log belongs to whichever day writes to it first
+        if current.size == 0 {
+            current.day = today;
+        }
+
+        if (current.size > 0 && current.size + json.len() as u64 > MAX_LOG_FILE_SIZE) || current.day != today {
+            let mut old_writer = log_writer.take().unwrap();
+            old_writer.writer.flush().ok(); // ignore error
+            let rotated_path = rotated_log_file_path(&self.log_file_path, old_writer.day);
+            drop(old_writer);
+            fs::rename(&self.log_file_path, rotated_path).ok(); // ignore error
+        }
+
+        let log_writer = log_writer.get_or_insert_with(|| open_log_writer(&self.log_file_path));
+        if log_writer.size == 0 {
+            log_writer.day = today;
+        }
+
+        if log_writer.writer.write_all(json.as_bytes()).is_ok() {
+            log_writer.size += json.len() as u64;
+        }
     }
 }
 
 #[rocket::async_trait]
 impl Fairing for ServerLogger {
-    fn info(&self) -> rocket::fairing::Info {
-        rocket::fairing::Info {
+    fn info(&self) -> Info {
+        return Info {
             name: "ServerLogger",
-            kind: rocket::fairing::Kind::Request
+            kind: Kind::Liftoff | Kind::Request | Kind::Response | Kind::Shutdown,
+        };
+    }
+
+    async fn on_liftoff(&self, _rocket: &Rocket<Orbit>) {
+        let log_writer = self.log_writer.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                Self::flush(&log_writer);
+            }
+        });
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let start = request.local_cache(|| RequestStart(Instant::now()));
+        let now = Utc::now();
+        self.write_line(now, &LogLine {
+            time: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            method: request.method().to_string(),
+            uri: request.uri().to_string(),
+            status: response.status().code,
+            latency_ms: start.0.elapsed().as_secs_f64() * 1000.0,
+            client: request.client_ip().map(|ip| ip.to_string()),
+        });
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        Self::flush(&self.log_writer);
+    }
+}
+
+// The current log and the rotated ones next to it
+fn list_log_files(log_file_path: &Path) -> Vec<PathBuf> {
+    let dir = match log_file_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = log_file_path.file_name().unwrap().to_string_lossy().to_string();
+    let rotated_prefix = format!("{file_name}.");
+
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == file_name || name.starts_with(&rotated_prefix) {
+                files.push(entry.path());
+            }
         }
     }
+    files.sort();
+    return files;
+}
+
+fn print_top_counts(title: &str, counts: &HashMap<String, u64>, top: usize) {
+    println!("{title}:");
+    if counts.is_empty() {
+        println!("  none");
+    }
+    for (name, count) in counts.iter().sorted_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0))).take(top) {
+        println!("  {count:>8}  {name}");
+    }
+}
+
+// Prints the drivers and teams requested most often, going by the query parameters
+// of the logged api requests. Lines in the old plain text format are skipped.
+pub fn print_log_report(log_file_path: &Path, top: usize) {
+    let mut drivers: HashMap<String, u64> = HashMap::new();
+    let mut site_teams: HashMap<String, u64> = HashMap::new();
+    let mut teams: HashMap<String, u64> = HashMap::new();
+    let mut request_count = 0;
 
-    async fn on_request(&self, request: &mut Request<'_>, _: &mut rocket::Data<'_>) {
-        let uri = request.uri();
-        let now = chrono::offset::Utc::now();
-        let mut log_file = OpenOptions::new().append(true).create(true).open(&self.log_file_path).unwrap();
-        writeln!(log_file, "{:?} request to {}", now, uri).ok(); // ignore error
-        log_file.flush().ok(); // ignore error
+    for path in list_log_files(log_file_path) {
+        let file = File::open(&path).unwrap();
+        for line in BufReader::new(file).lines() {
+            let Ok(line) = line else {
+                continue;
+            };
+            let Ok(log_line) = serde_json::from_str::<LogLine>(&line) else {
+                continue;
+            };
+            request_count += 1;
+
+            let Ok(url) = reqwest::Url::parse(&format!("http://localhost{}", log_line.uri)) else {
+                continue;
+            };
+            for (name, value) in url.query_pairs() {
+                match name.as_ref() {
+                    "driver_name" | "cust_id" => {
+                        *drivers.entry(value.to_string()).or_insert(0) += 1;
+                    },
+                    "drivers" | "cust_ids" => {
+                        for driver in value.split(';').filter(|driver| !driver.is_empty()) {
+                            *drivers.entry(driver.to_owned()).or_insert(0) += 1;
+                        }
+                    },
+                    "site_team" | "team" => {
+                        *site_teams.entry(value.to_string()).or_insert(0) += 1;
+                    },
+                    "team_id" | "team_ids" => {
+                        for team in value.split(';').filter(|team| !team.is_empty()) {
+                            *teams.entry(team.to_owned()).or_insert(0) += 1;
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
     }
+
+    println!("{request_count} logged requests");
+    print_top_counts("Most requested drivers", &drivers, top);
+    print_top_counts("Most requested site teams", &site_teams, top);
+    print_top_counts("Most requested teams", &teams, top);
 }
@@ -0,0 +1,193 @@
+// Append-only packed storage for cached subsession JSONs.
+//
+// Subsessions are bucketed by id range. Each bucket has a `.pack` file holding
+// deflate compressed JSON records back to back, and an `.idx` file of fixed
+// size entries pointing into it. Rewriting a subsession appends a new record,
+// the last index entry wins.
+//
+// The server and the cron syncs both write, so appends hold a `.lock` file per
+// bucket. Each process caches the indices, a cache that is behind the index
+// file is reloaded before appending or when it misses a subsession.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use lazy_static::lazy_static;
+
+use crate::db::get_session_packs_dir;
+
+const SUBSESSIONS_PER_BUCKET: i64 = 1_000_000;
+
+// subsession_id (i64) + offset (u64) + length (u32)
+const INDEX_ENTRY_SIZE: usize = 20;
+
+#[derive(Clone, Copy)]
+struct PackEntry {
+    offset: u64,
+    length: u32,
+}
+
+struct BucketIndex {
+    entries: HashMap<i64, PackEntry>,
+    // length of the index file the entries were read from
+    file_len: u64,
+}
+
+lazy_static! {
+    // bucket -> index; buckets are loaded lazily
+    static ref INDEX: Mutex<HashMap<i64, BucketIndex>> = Mutex::new(HashMap::new());
+}
+
+fn get_bucket(subsession_id: i64) -> i64 {
+    return subsession_id / SUBSESSIONS_PER_BUCKET;
+}
+
+fn get_pack_path(bucket: i64) -> PathBuf {
+    return get_session_packs_dir().join(format!("{bucket:04}.pack"));
+}
+
+fn get_index_path(bucket: i64) -> PathBuf {
+    return get_session_packs_dir().join(format!("{bucket:04}.idx"));
+}
+
+fn get_lock_path(bucket: i64) -> PathBuf {
+    return get_session_packs_dir().join(format!("{bucket:04}.lock"));
+}
+
+fn load_bucket_index(bucket: i64) -> BucketIndex {
+    let mut entries = HashMap::new();
+
+    let Ok(bytes) = fs::read(get_index_path(bucket)) else {
+        return BucketIndex{ entries, file_len: 0 };
+    };
+
+    // a torn entry at the end (interrupted write) is ignored
+    for entry in bytes.chunks_exact(INDEX_ENTRY_SIZE) {
+        let subsession_id = i64::from_le_bytes(entry[0..8].try_into().unwrap());
+        let offset = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+        let length = u32::from_le_bytes(entry[16..20].try_into().unwrap());
+        entries.insert(subsession_id, PackEntry{ offset, length });
+    }
+    return BucketIndex{ entries, file_len: bytes.len() as u64 };
+}
+
+fn index_file_len(bucket: i64) -> u64 {
+    return fs::metadata(get_index_path(bucket)).map_or(0, |metadata| metadata.len());
+}
+
+// another process appended since the index was loaded
+fn reload_if_behind(bucket: i64, index: &mut BucketIndex) {
+    if index_file_len(bucket) != index.file_len {
+        *index = load_bucket_index(bucket);
+    }
+}
+
+fn with_bucket_index<T, F>(bucket: i64, f: F) -> T
+    where F: FnOnce(&mut BucketIndex) -> T
+{
+    let mut index = INDEX.lock().unwrap();
+    let bucket_index = index.entry(bucket).or_insert_with(|| load_bucket_index(bucket));
+    return f(bucket_index);
+}
+
+fn find_entry(subsession_id: i64) -> Option<PackEntry> {
+    let bucket = get_bucket(subsession_id);
+    return with_bucket_index(bucket, |index| {
+        if !index.entries.contains_key(&subsession_id) {
+            reload_if_behind(bucket, index);
+        }
+        return index.entries.get(&subsession_id).copied();
+    });
+}
+
+pub fn contains(subsession_id: i64) -> bool {
+    return find_entry(subsession_id).is_some();
+}
+
+pub fn read(subsession_id: i64) -> Option<String> {
+    let entry = find_entry(subsession_id)?;
+
+    let mut pack = File::open(get_pack_path(get_bucket(subsession_id))).unwrap();
+    pack.seek(SeekFrom::Start(entry.offset)).unwrap();
+
+    let mut compressed = vec![0u8; entry.length as usize];
+    pack.read_exact(&mut compressed).unwrap();
+
+    let mut content = String::new();
+    DeflateDecoder::new(compressed.as_slice()).read_to_string(&mut content).unwrap();
+    return Some(content);
+}
+
+pub fn write(subsession_id: i64, content: &str) {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(content.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let bucket = get_bucket(subsession_id);
+
+    // the locks are held for the whole append so offsets stay consistent,
+    // the mutex for this process' threads and the lock file for other processes
+    with_bucket_index(bucket, |index| {
+        let lock_file = OpenOptions::new().create(true).truncate(false).write(true).open(get_lock_path(bucket)).unwrap();
+        lock_file.lock().unwrap();
+
+        // an entry torn by an interrupted write would misalign every entry appended after it
+        let mut index_file = OpenOptions::new().create(true).truncate(false).write(true).open(get_index_path(bucket)).unwrap();
+        let index_len = index_file.metadata().unwrap().len();
+        let whole_entries_len = index_len - index_len % INDEX_ENTRY_SIZE as u64;
+        if whole_entries_len != index_len {
+            println!("Dropping a torn entry at the end of {}", get_index_path(bucket).display());
+            index_file.set_len(whole_entries_len).unwrap();
+        }
+        reload_if_behind(bucket, index);
+
+        let mut pack = OpenOptions::new().create(true).append(true).open(get_pack_path(bucket)).unwrap();
+        let offset = pack.seek(SeekFrom::End(0)).unwrap();
+        pack.write_all(&compressed).unwrap();
+
+        let entry = PackEntry{ offset, length: compressed.len() as u32 };
+
+        // the index is only appended once the data is in place
+        let mut index_entry = Vec::with_capacity(INDEX_ENTRY_SIZE);
+        index_entry.extend_from_slice(&subsession_id.to_le_bytes());
+        index_entry.extend_from_slice(&entry.offset.to_le_bytes());
+        index_entry.extend_from_slice(&entry.length.to_le_bytes());
+
+        index_file.seek(SeekFrom::Start(whole_entries_len)).unwrap();
+        index_file.write_all(&index_entry).unwrap();
+
+        index.entries.insert(subsession_id, entry);
+        index.file_len = whole_entries_len + INDEX_ENTRY_SIZE as u64;
+        // the lock file is unlocked when it is closed
+    });
+}
+
+pub fn list_subsession_ids() -> Vec<i64> {
+    let mut buckets = Vec::new();
+    for dir_entry in fs::read_dir(get_session_packs_dir()).unwrap() {
+        let path = dir_entry.unwrap().path();
+        if path.extension().unwrap_or_default() != "idx" {
+            continue;
+        }
+        if let Some(bucket) = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<i64>().ok()) {
+            buckets.push(bucket);
+        }
+    }
+    buckets.sort_unstable();
+
+    let mut subsession_ids = Vec::new();
+    for bucket in buckets {
+        let mut bucket_ids = with_bucket_index(bucket, |index| {
+            reload_if_behind(bucket, index);
+            return index.entries.keys().copied().collect::<Vec<i64>>();
+        });
+        bucket_ids.sort_unstable();
+        subsession_ids.append(&mut bucket_ids);
+    }
+    return subsession_ids;
+}
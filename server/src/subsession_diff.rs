@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use serde_json::Value;
+
+// Fields of a driver result that iRacing may amend after the fact (penalties, DQs)
+const TRACKED_FIELDS: [&str; 9] = [
+    "finish_position",
+    "finish_position_in_class",
+    "reason_out",
+    "champ_points",
+    "incidents",
+    "laps_complete",
+    "newi_rating",
+    "new_cpi",
+    "average_lap",
+];
+
+// (simsession_number, cust_id) -> driver result
+fn collect_driver_results(subsession: &Value) -> BTreeMap<(i64, i64), &Value> {
+    let mut results = BTreeMap::new();
+
+    for simsession in subsession["session_results"].as_array().unwrap() {
+        let simsession_number = simsession["simsession_number"].as_i64().unwrap();
+
+        for participant in simsession["results"].as_array().unwrap() {
+            if let Some(cust_id) = participant["cust_id"].as_i64() {
+                results.insert((simsession_number, cust_id), participant);
+            } else if let Some(drivers) = participant["driver_results"].as_array() {
+                for driver in drivers {
+                    results.insert((simsession_number, driver["cust_id"].as_i64().unwrap()), driver);
+                }
+            }
+        }
+    }
+    return results;
+}
+
+fn display_name(driver_result: &Value) -> &str {
+    return driver_result["display_name"].as_str().unwrap_or("?");
+}
+
+// Returns a human readable line for every difference between two versions of the same subsession
+pub fn diff_subsessions(old: &Value, new: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if old == new {
+        return changes;
+    }
+
+    let old_results = collect_driver_results(old);
+    let new_results = collect_driver_results(new);
+
+    for ((simsession_number, cust_id), old_result) in &old_results {
+        let Some(new_result) = new_results.get(&(*simsession_number, *cust_id)) else {
+            changes.push(format!("{} ({cust_id}) removed from simsession {simsession_number}", display_name(old_result)));
+            continue;
+        };
+
+        for field in TRACKED_FIELDS {
+            if old_result[field] != new_result[field] {
+                changes.push(format!("{} ({cust_id}) simsession {simsession_number}: {field} {} -> {}",
+                    display_name(new_result),
+                    old_result[field],
+                    new_result[field]
+                ));
+            }
+        }
+    }
+
+    for ((simsession_number, cust_id), new_result) in &new_results {
+        if !old_results.contains_key(&(*simsession_number, *cust_id)) {
+            changes.push(format!("{} ({cust_id}) added to simsession {simsession_number}", display_name(new_result)));
+        }
+    }
+
+    if changes.is_empty() {
+        changes.push("untracked fields changed".to_owned());
+    }
+
+    return changes;
+}
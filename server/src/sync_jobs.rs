@@ -0,0 +1,209 @@
+// Background syncs requested through the api (POST /api/v1/sync-jobs).
+//
+// The sync_job table is the queue. One worker task runs the jobs one at a time on
+// the server's shared IRacingClient, so api syncs never compete with each other for
+// the rate limit. Progress and errors are written back to the job for the site to poll,
+// and published as `SyncJobEvent`s for /api/v1/sync-job-events to stream.
+
+use std::any::Any;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use rusqlite::Connection;
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc, Notify};
+use utoipa::ToSchema;
+
+use crate::db::{
+    add_sync_job_to_db,
+    create_db_connection,
+    fail_interrupted_sync_jobs,
+    finish_sync_job,
+    set_sync_job_progress,
+    start_next_sync_job,
+    SyncJob,
+    SyncJobRequest,
+};
+use crate::iracing_client::{self, IRacingClient, ProgressSender};
+
+// subscribers that fall further behind than this skip events
+const EVENT_CAPACITY: usize = 256;
+// wait before trying job bookkeeping again, when the db was busy or locked
+const DB_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, ToSchema, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SyncJobEvent {
+    Queued { sync_job_id: i64 },
+    Started { sync_job_id: i64 },
+    Progress {
+        sync_job_id: i64,
+        /// searching or downloading
+        stage: String,
+        done: usize,
+        total: usize,
+        /// of the current stage so far
+        per_second: f64,
+    },
+    Finished {
+        sync_job_id: i64,
+        /// done or failed
+        status: String,
+        error: Option<String>,
+    },
+}
+
+impl SyncJobEvent {
+    pub fn sync_job_id(&self) -> i64 {
+        return match self {
+            SyncJobEvent::Queued{ sync_job_id }
+            | SyncJobEvent::Started{ sync_job_id }
+            | SyncJobEvent::Progress{ sync_job_id, .. }
+            | SyncJobEvent::Finished{ sync_job_id, .. } => *sync_job_id,
+        };
+    }
+
+    // the SSE event name, same as the "event" field of the data
+    pub fn name(&self) -> &'static str {
+        return match self {
+            SyncJobEvent::Queued{ .. } => "queued",
+            SyncJobEvent::Started{ .. } => "started",
+            SyncJobEvent::Progress{ .. } => "progress",
+            SyncJobEvent::Finished{ .. } => "finished",
+        };
+    }
+}
+
+pub struct SyncJobWorker {
+    notify: Arc<Notify>,
+    events: broadcast::Sender<SyncJobEvent>,
+}
+
+impl SyncJobWorker {
+    // Has to be called from within the tokio runtime
+    pub fn start(client: Arc<IRacingClient>) -> Self {
+        fail_interrupted_sync_jobs(&create_db_connection());
+
+        let notify = Arc::new(Notify::new());
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        tokio::spawn(run_worker(client, notify.clone(), events.clone()));
+        return SyncJobWorker{ notify, events };
+    }
+
+    pub fn enqueue(&self, con: &Connection, request: &SyncJobRequest, requested_by: &str) -> SyncJob {
+        let job = add_sync_job_to_db(con, request, requested_by);
+        publish(&self.events, SyncJobEvent::Queued{ sync_job_id: job.sync_job_id });
+        self.notify.notify_one();
+        return job;
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncJobEvent> {
+        return self.events.subscribe();
+    }
+}
+
+fn publish(events: &broadcast::Sender<SyncJobEvent>, event: SyncJobEvent) {
+    events.send(event).ok(); // no subscribers is fine
+}
+
+async fn run_worker(client: Arc<IRacingClient>, notify: Arc<Notify>, events: broadcast::Sender<SyncJobEvent>) {
+    loop {
+        let job = start_next_sync_job(&mut create_db_connection());
+        match job {
+            Ok(Some(job)) => run_sync_job(&client, job, &events).await,
+            // a notify_one() while a job runs is kept, so no job is missed
+            Ok(None) => notify.notified().await,
+            Err(error) => {
+                println!("Could not start the next sync job, trying again: {error}");
+                tokio::time::sleep(DB_RETRY_DELAY).await;
+            },
+        }
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<String>() {
+        return message.clone();
+    }
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    return "Sync panicked".to_owned();
+}
+
+async fn run_sync_job(client: &Arc<IRacingClient>, job: SyncJob, events: &broadcast::Sender<SyncJobEvent>) {
+    println!("Running sync job {} requested by {}", job.sync_job_id, job.requested_by);
+    let con = create_db_connection();
+    publish(events, SyncJobEvent::Started{ sync_job_id: job.sync_job_id });
+
+    // The syncs panic when iRacing keeps failing, running them in their own task
+    // turns that into a failed job instead of a dead worker
+    let (progress_sender, mut progress_receiver) = mpsc::unbounded_channel();
+    let task = tokio::spawn(run_sync_request(client.clone(), job.request, progress_sender));
+
+    // ends when the task drops the sender, whether it finished or panicked
+    let mut stage_start = (Instant::now(), "");
+    while let Some(progress) = progress_receiver.recv().await {
+        // the next progress update tries again
+        if let Err(error) = set_sync_job_progress(&con, job.sync_job_id, progress.stage, progress.done, progress.total) {
+            println!("Could not save the progress of sync job {}: {error}", job.sync_job_id);
+        }
+
+        if stage_start.1 != progress.stage {
+            stage_start = (Instant::now(), progress.stage);
+        }
+        let elapsed_secs = stage_start.0.elapsed().as_secs_f64();
+        publish(events, SyncJobEvent::Progress{
+            sync_job_id: job.sync_job_id,
+            stage: progress.stage.to_owned(),
+            done: progress.done,
+            total: progress.total,
+            per_second: if elapsed_secs > 0.0 { progress.done as f64 / elapsed_secs } else { 0.0 },
+        });
+    }
+
+    let error = match task.await {
+        Ok(()) => None,
+        Err(error) if error.is_panic() => Some(panic_message(error.into_panic())),
+        Err(error) => Some(error.to_string()),
+    };
+    match &error {
+        Some(error) => println!("Sync job {} failed: {error}", job.sync_job_id),
+        None => println!("Sync job {} done", job.sync_job_id),
+    }
+    // a job left running would never be finished
+    while let Err(db_error) = finish_sync_job(&con, job.sync_job_id, &error) {
+        println!("Could not finish sync job {}, trying again: {db_error}", job.sync_job_id);
+        tokio::time::sleep(DB_RETRY_DELAY).await;
+    }
+    publish(events, SyncJobEvent::Finished{
+        sync_job_id: job.sync_job_id,
+        status: if error.is_some() { "failed" } else { "done" }.to_owned(),
+        error,
+    });
+}
+
+async fn run_sync_request(client: Arc<IRacingClient>, request: SyncJobRequest, progress: ProgressSender) {
+    let progress = Some(&progress);
+    match request {
+        SyncJobRequest::CustIds{ cust_ids, partial } => {
+            if partial {
+                iracing_client::sync_cust_ids_to_db(&client, &Vec::new(), &cust_ids, progress).await;
+            } else {
+                iracing_client::sync_cust_ids_to_db(&client, &cust_ids, &Vec::new(), progress).await;
+            }
+        },
+        SyncJobRequest::SubsessionIds{ subsession_ids } => {
+            iracing_client::sync_subsessions_to_db(&client, subsession_ids, progress).await;
+        },
+        SyncJobRequest::Season{ year, quarter, week } => {
+            iracing_client::sync_season_to_db(&client, year, quarter, week, progress).await;
+        },
+        SyncJobRequest::CarInfos => {
+            iracing_client::sync_car_infos_to_db(&client).await;
+            iracing_client::sync_car_class_infos_to_db(&client).await;
+        },
+        SyncJobRequest::TrackInfos => {
+            iracing_client::sync_track_infos_to_db(&client).await;
+        },
+    }
+}
@@ -0,0 +1,254 @@
+// Minimal XLSX (Office Open XML spreadsheet) writer for the report exports.
+// Only what the exports need: text and number cells, a bold header row.
+
+use std::io::{Cursor, Write};
+use simple_xml_builder::XMLElement;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+const SPREADSHEET_NS: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const RELATIONSHIPS_NS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const OFFICE_RELATIONSHIPS_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+// index into cellXfs of styles.xml
+const BOLD_STYLE: &str = "1";
+
+pub enum Cell {
+    Text(String),
+    Number(f64),
+}
+
+pub struct Sheet {
+    // at most 31 characters, none of []:*?/\
+    pub name: String,
+    pub header: Vec<&'static str>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+// 0 -> A, 25 -> Z, 26 -> AA
+fn column_name(mut column: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (column % 26) as u8);
+        if column < 26 {
+            break;
+        }
+        column = column / 26 - 1;
+    }
+    name.reverse();
+    return String::from_utf8(name).unwrap();
+}
+
+fn text_cell(reference: String, text: &str) -> XMLElement {
+    let mut cell = XMLElement::new("c");
+    cell.add_attribute("r", reference);
+    cell.add_attribute("t", "inlineStr");
+
+    let mut t = XMLElement::new("t");
+    t.add_attribute("xml:space", "preserve");
+    t.add_text(text);
+
+    let mut inline_string = XMLElement::new("is");
+    inline_string.add_child(t);
+    cell.add_child(inline_string);
+    return cell;
+}
+
+fn number_cell(reference: String, number: f64) -> XMLElement {
+    let mut cell = XMLElement::new("c");
+    cell.add_attribute("r", reference);
+
+    let mut value = XMLElement::new("v");
+    value.add_text(number);
+    cell.add_child(value);
+    return cell;
+}
+
+fn sheet_xml(sheet: &Sheet) -> XMLElement {
+    let mut worksheet = XMLElement::new("worksheet");
+    worksheet.add_attribute("xmlns", SPREADSHEET_NS);
+
+    let mut cols = XMLElement::new("cols");
+    for i in 0..sheet.header.len() {
+        let mut col = XMLElement::new("col");
+        col.add_attribute("min", i + 1);
+        col.add_attribute("max", i + 1);
+        col.add_attribute("width", 20);
+        col.add_attribute("customWidth", 1);
+        cols.add_child(col);
+    }
+    if !sheet.header.is_empty() {
+        worksheet.add_child(cols);
+    }
+
+    let mut sheet_data = XMLElement::new("sheetData");
+
+    let mut header_row = XMLElement::new("row");
+    header_row.add_attribute("r", 1);
+    for (i, title) in sheet.header.iter().enumerate() {
+        let mut cell = text_cell(format!("{}1", column_name(i)), title);
+        cell.add_attribute("s", BOLD_STYLE);
+        header_row.add_child(cell);
+    }
+    sheet_data.add_child(header_row);
+
+    for (row_index, row) in sheet.rows.iter().enumerate() {
+        let row_number = row_index + 2;
+        let mut row_element = XMLElement::new("row");
+        row_element.add_attribute("r", row_number);
+        for (i, cell) in row.iter().enumerate() {
+            let reference = format!("{}{row_number}", column_name(i));
+            row_element.add_child(match cell {
+                Cell::Text(text) => text_cell(reference, text),
+                Cell::Number(number) => number_cell(reference, *number),
+            });
+        }
+        sheet_data.add_child(row_element);
+    }
+
+    worksheet.add_child(sheet_data);
+    return worksheet;
+}
+
+fn content_types_xml(sheet_count: usize) -> XMLElement {
+    let mut types = XMLElement::new("Types");
+    types.add_attribute("xmlns", "http://schemas.openxmlformats.org/package/2006/content-types");
+
+    let mut rels = XMLElement::new("Default");
+    rels.add_attribute("Extension", "rels");
+    rels.add_attribute("ContentType", "application/vnd.openxmlformats-package.relationships+xml");
+    types.add_child(rels);
+
+    let mut xml = XMLElement::new("Default");
+    xml.add_attribute("Extension", "xml");
+    xml.add_attribute("ContentType", "application/xml");
+    types.add_child(xml);
+
+    let mut workbook = XMLElement::new("Override");
+    workbook.add_attribute("PartName", "/xl/workbook.xml");
+    workbook.add_attribute("ContentType", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml");
+    types.add_child(workbook);
+
+    let mut styles = XMLElement::new("Override");
+    styles.add_attribute("PartName", "/xl/styles.xml");
+    styles.add_attribute("ContentType", "application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml");
+    types.add_child(styles);
+
+    for i in 1..=sheet_count {
+        let mut sheet = XMLElement::new("Override");
+        sheet.add_attribute("PartName", format!("/xl/worksheets/sheet{i}.xml"));
+        sheet.add_attribute("ContentType", "application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml");
+        types.add_child(sheet);
+    }
+    return types;
+}
+
+fn relationship(id: &str, relationship_type: &str, target: &str) -> XMLElement {
+    let mut element = XMLElement::new("Relationship");
+    element.add_attribute("Id", id);
+    element.add_attribute("Type", format!("http://schemas.openxmlformats.org/officeDocument/2006/relationships/{relationship_type}"));
+    element.add_attribute("Target", target);
+    return element;
+}
+
+fn root_rels_xml() -> XMLElement {
+    let mut relationships = XMLElement::new("Relationships");
+    relationships.add_attribute("xmlns", RELATIONSHIPS_NS);
+    relationships.add_child(relationship("rId1", "officeDocument", "xl/workbook.xml"));
+    return relationships;
+}
+
+fn workbook_rels_xml(sheet_count: usize) -> XMLElement {
+    let mut relationships = XMLElement::new("Relationships");
+    relationships.add_attribute("xmlns", RELATIONSHIPS_NS);
+    for i in 1..=sheet_count {
+        relationships.add_child(relationship(&format!("rId{i}"), "worksheet", &format!("worksheets/sheet{i}.xml")));
+    }
+    relationships.add_child(relationship(&format!("rId{}", sheet_count + 1), "styles", "styles.xml"));
+    return relationships;
+}
+
+fn workbook_xml(sheets: &Vec<Sheet>) -> XMLElement {
+    let mut workbook = XMLElement::new("workbook");
+    workbook.add_attribute("xmlns", SPREADSHEET_NS);
+    workbook.add_attribute("xmlns:r", OFFICE_RELATIONSHIPS_NS);
+
+    let mut sheets_element = XMLElement::new("sheets");
+    for (i, sheet) in sheets.iter().enumerate() {
+        let mut sheet_element = XMLElement::new("sheet");
+        sheet_element.add_attribute("name", &sheet.name);
+        sheet_element.add_attribute("sheetId", i + 1);
+        sheet_element.add_attribute("r:id", format!("rId{}", i + 1));
+        sheets_element.add_child(sheet_element);
+    }
+    workbook.add_child(sheets_element);
+    return workbook;
+}
+
+fn styles_xml() -> XMLElement {
+    let mut style_sheet = XMLElement::new("styleSheet");
+    style_sheet.add_attribute("xmlns", SPREADSHEET_NS);
+
+    let mut fonts = XMLElement::new("fonts");
+    fonts.add_attribute("count", 2);
+    fonts.add_child(XMLElement::new("font"));
+    let mut bold_font = XMLElement::new("font");
+    bold_font.add_child(XMLElement::new("b"));
+    fonts.add_child(bold_font);
+    style_sheet.add_child(fonts);
+
+    // Excel expects the two reserved fills to be present
+    let mut fills = XMLElement::new("fills");
+    fills.add_attribute("count", 2);
+    for pattern_type in ["none", "gray125"] {
+        let mut fill = XMLElement::new("fill");
+        let mut pattern_fill = XMLElement::new("patternFill");
+        pattern_fill.add_attribute("patternType", pattern_type);
+        fill.add_child(pattern_fill);
+        fills.add_child(fill);
+    }
+    style_sheet.add_child(fills);
+
+    let mut borders = XMLElement::new("borders");
+    borders.add_attribute("count", 1);
+    borders.add_child(XMLElement::new("border"));
+    style_sheet.add_child(borders);
+
+    let mut cell_style_xfs = XMLElement::new("cellStyleXfs");
+    cell_style_xfs.add_attribute("count", 1);
+    cell_style_xfs.add_child(XMLElement::new("xf"));
+    style_sheet.add_child(cell_style_xfs);
+
+    let mut cell_xfs = XMLElement::new("cellXfs");
+    cell_xfs.add_attribute("count", 2);
+    cell_xfs.add_child(XMLElement::new("xf"));
+    let mut bold_xf = XMLElement::new("xf");
+    bold_xf.add_attribute("fontId", 1);
+    bold_xf.add_attribute("applyFont", 1);
+    cell_xfs.add_child(bold_xf);
+    style_sheet.add_child(cell_xfs);
+
+    return style_sheet;
+}
+
+fn add_xml_file(zip: &mut ZipWriter<Cursor<Vec<u8>>>, path: &str, element: XMLElement) {
+    zip.start_file(path, FileOptions::default()).unwrap();
+    let mut content = Vec::new();
+    element.write(&mut content).unwrap();
+    zip.write_all(&content).unwrap();
+}
+
+pub fn write_workbook(sheets: &Vec<Sheet>) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    add_xml_file(&mut zip, "[Content_Types].xml", content_types_xml(sheets.len()));
+    add_xml_file(&mut zip, "_rels/.rels", root_rels_xml());
+    add_xml_file(&mut zip, "xl/workbook.xml", workbook_xml(sheets));
+    add_xml_file(&mut zip, "xl/_rels/workbook.xml.rels", workbook_rels_xml(sheets.len()));
+    add_xml_file(&mut zip, "xl/styles.xml", styles_xml());
+    for (i, sheet) in sheets.iter().enumerate() {
+        add_xml_file(&mut zip, &format!("xl/worksheets/sheet{}.xml", i + 1), sheet_xml(sheet));
+    }
+
+    return zip.finish().unwrap().into_inner();
+}